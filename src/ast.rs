@@ -1,4 +1,4 @@
-use crate::symbols::ProcedureSymbol;
+use crate::symbols::{Guard, ProcedureSymbol, Symbol};
 use crate::tokens::{Token, Value};
 
 // TODO: Use multiple enums?
@@ -13,12 +13,27 @@ pub enum Node {
     Var(Var),
     Program(String, Box<Block>),
     // Block(Box<Block>),
-    VarDecl(Var, Type),
+    /// A variable with its type, and the initial value Free Pascal allows
+    /// in `x: INTEGER = 5`
+    VarDecl(Var, Type, Option<Box<Node>>),
+    /// A constant's name, its type if one is given, and its value
+    ConstDecl(Token, Option<Type>, Box<Node>),
+    /// A type's name and the type it stands for
+    TypeDecl(Token, Type),
     // Type(Type),
-    ProcedureDecl(String, Box<Block>, Vec<Param>),
+    ProcedureDecl(ProcedureDecl),
     // Param(Param),
     ProcedureCall(ProcedureCall),
     // Block(Box<Block>),
+    If(Token, Box<Node>, Box<Node>, Option<Box<Node>>),
+    While(Token, Box<Node>, Box<Node>),
+    Repeat(Token, Vec<Node>, Box<Node>),
+    For(Var, Box<Node>, Token, Box<Node>, Box<Node>),
+    Case(Token, Box<Node>, Vec<CaseArm>, Option<Vec<Node>>),
+    /// A set constructor like `[1, 3..5, c]`
+    Set(Token, Vec<(Node, Option<Node>)>),
+    /// An argument with a field width and optional precision, like `x:8:2`
+    Formatted(Token, Box<Node>, Box<Node>, Option<Box<Node>>),
     NoOp,
 }
 
@@ -38,20 +53,125 @@ impl Block {
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct Type {
-    pub value: Value,
+pub enum Type {
+    /// A builtin or declared type referred to by name
+    Named(Token),
+    /// An enumerated type like `(Red, Green, Blue)`
+    Enum(Token, Vec<Token>),
+    Subrange(Token, Box<Node>, Box<Node>),
+    /// Index type and element type
+    Array(Token, Box<Type>, Box<Type>),
+    /// `ARRAY OF T`, whose length is set at run time
+    DynArray(Token, Box<Type>),
+    /// `ARRAY[lo..hi: T] OF E` as a parameter, taking arrays with any bounds
+    /// of the index type, which `lo` and `hi` stand for
+    Conformant(Token, Token, Token, Box<Type>, Box<Type>),
+    /// `ARRAY OF E` as a parameter, taking arrays of any length indexed from
+    /// zero
+    OpenArray(Token, Box<Type>),
+    Record(Token, FieldList),
+    /// `STRING[n]`, with its maximum length
+    String(Token, Box<Node>),
+    /// `SET OF` an ordinal type
+    Set(Token, Box<Type>),
+    /// `^T`, with the name of the type pointed to
+    Pointer(Token, Token),
+    /// `FUNCTION(x: REAL): REAL`, the parameters and result type of the
+    /// routines it takes
+    Procedure(Token, Vec<Param>, Option<Box<Type>>),
 }
 
 impl Type {
     pub fn new(token: Token) -> Self {
-        Type { value: token.value }
+        Type::Named(token)
+    }
+
+    pub fn token(&self) -> &Token {
+        match self {
+            Type::Named(token)
+            | Type::Enum(token, _)
+            | Type::Subrange(token, ..)
+            | Type::Array(token, ..)
+            | Type::DynArray(token, _)
+            | Type::Conformant(token, ..)
+            | Type::OpenArray(token, _)
+            | Type::Record(token, ..)
+            | Type::String(token, ..)
+            | Type::Set(token, _)
+            | Type::Pointer(token, _)
+            | Type::Procedure(token, ..) => token,
+        }
+    }
+}
+
+/// The fields of a RECORD, or of one of its variants
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldList {
+    pub fields: Vec<(Token, Type)>,
+    pub variant_part: Option<Box<VariantPart>>,
+}
+
+impl FieldList {
+    pub fn new(fields: Vec<(Token, Type)>, variant_part: Option<VariantPart>) -> Self {
+        FieldList {
+            fields,
+            variant_part: variant_part.map(Box::new),
+        }
+    }
+}
+
+/// `CASE tag: T OF` and the variants it selects between. The tag field
+/// may be left out, as in `CASE BOOLEAN OF`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantPart {
+    pub token: Token,
+    pub tag: Option<Token>,
+    pub tag_type: Type,
+    pub variants: Vec<Variant>,
+}
+
+impl VariantPart {
+    pub fn new(token: Token, tag: Option<Token>, tag_type: Type, variants: Vec<Variant>) -> Self {
+        VariantPart {
+            token,
+            tag,
+            tag_type,
+            variants,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variant {
+    pub labels: Vec<(Node, Option<Node>)>,
+    pub fields: FieldList,
+}
+
+impl Variant {
+    pub fn new(labels: Vec<(Node, Option<Node>)>, fields: FieldList) -> Self {
+        Variant { labels, fields }
     }
 }
 
+/// One step of a designator after the variable name, such as `[i]` or `.x`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selector {
+    Index(Box<Node>),
+    /// An index counted from zero whatever the bounds of the array, which
+    /// the analyzer uses for open array parameters
+    Position(Box<Node>),
+    /// The field name, and the variants it is only accessible in
+    Field(Token, Vec<Guard>),
+    /// `^`, following a pointer to the variable it points to
+    Deref(Token),
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Var {
     pub token: Token,
     pub value: Value,
+    pub selectors: Vec<Selector>,
+    pub type_symbol: Option<Symbol>,
 }
 
 impl Var {
@@ -59,6 +179,8 @@ impl Var {
         Var {
             value: token.value.clone(),
             token,
+            selectors: Vec::new(),
+            type_symbol: None,
         }
     }
 }
@@ -84,13 +206,53 @@ impl Compound {
 pub struct Param {
     pub var_node: Var,
     pub type_node: Type,
+    pub by_reference: bool,
 }
 
 impl Param {
-    pub fn new(var_node: Var, type_node: Type) -> Self {
+    pub fn new(var_node: Var, type_node: Type, by_reference: bool) -> Self {
         Param {
             var_node,
             type_node,
+            by_reference,
+        }
+    }
+}
+
+/// A PROCEDURE, or a FUNCTION when it has a return type
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcedureDecl {
+    pub name: String,
+    /// The name in the heading, where errors about the declaration are
+    /// reported
+    pub token: Token,
+    pub params: Vec<Param>,
+    pub return_type: Option<Type>,
+    /// Whether it's declared with FUNCTION. The definition of a FORWARD
+    /// function may leave out its result type along with its parameters.
+    pub function: bool,
+    /// None for a FORWARD declaration, whose block follows later
+    pub block: Option<Box<Block>>,
+    /// Set by the analyzer when `Result` stands for the function result in
+    /// the block, as in Turbo Pascal
+    pub result_alias: bool,
+}
+
+impl ProcedureDecl {
+    pub fn new(
+        token: Token,
+        params: Vec<Param>,
+        return_type: Option<Type>,
+        block: Option<Block>,
+    ) -> Self {
+        ProcedureDecl {
+            name: token.value.expect_string(),
+            token,
+            function: return_type.is_some(),
+            params,
+            return_type,
+            block: block.map(Box::new),
+            result_alias: false,
         }
     }
 }
@@ -113,3 +275,16 @@ impl ProcedureCall {
         }
     }
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseArm {
+    /// Each label is a constant or an inclusive `low..high` range
+    pub labels: Vec<(Node, Option<Node>)>,
+    pub statement: Node,
+}
+
+impl CaseArm {
+    pub fn new(labels: Vec<(Node, Option<Node>)>, statement: Node) -> Self {
+        CaseArm { labels, statement }
+    }
+}
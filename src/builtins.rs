@@ -0,0 +1,474 @@
+//! Procedures and functions that every program can call without declaring
+//! them. The analyzer checks calls against `formals` and `return_type`, and
+//! the interpreter evaluates them with the helpers below.
+
+use crate::error::RuntimeError;
+use crate::symbols::{FieldSymbol, Guard, Symbol};
+use crate::tokens::Value;
+use std::f32::consts::PI;
+
+/// The flavour of Pascal a program is written in, which decides the
+/// extensions available to it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    /// ISO 7185 Standard Pascal
+    Iso,
+    /// Turbo Pascal and Free Pascal
+    Turbo,
+}
+
+pub const ROUTINES: &[&str] = &[
+    "Length",
+    "Copy",
+    "Pos",
+    "Concat",
+    "Insert",
+    "Delete",
+    "UpperCase",
+    "LowerCase",
+    "IntToStr",
+    "StrToInt",
+    "Val",
+    "Str",
+    "Ord",
+    "Chr",
+    "Succ",
+    "Pred",
+    "Write",
+    "WriteLn",
+    "Read",
+    "ReadLn",
+    "Abs",
+    "Sqr",
+    "Sqrt",
+    "Sin",
+    "Cos",
+    "ArcTan",
+    "Exp",
+    "Ln",
+    "Round",
+    "Trunc",
+    "Odd",
+    "New",
+    "Dispose",
+];
+
+/// Routines that only exist in the Turbo dialect
+pub const TURBO_ROUTINES: &[&str] = &[
+    "Pi",
+    "Power",
+    "Floor",
+    "Ceil",
+    "Inc",
+    "Dec",
+    "Low",
+    "High",
+    "SizeOf",
+    "SetLength",
+];
+
+/// What a builtin routine accepts for one of its parameters
+#[derive(Clone, Copy)]
+pub enum Formal {
+    /// An expression whose type passes the check
+    Value(fn(&Symbol) -> bool),
+    /// A variable whose type passes the check, which the routine may change
+    Var(fn(&Symbol) -> bool),
+    /// A type, or a variable whose type passes the check. Only the type is
+    /// looked at, so the variable may well be undefined.
+    Type(fn(&Symbol) -> bool),
+}
+
+fn is_integer(type_: &Symbol) -> bool {
+    type_.is_builtin("INTEGER")
+}
+
+fn is_writable(type_: &Symbol) -> bool {
+    type_.is_numeric() || type_.is_builtin("BOOLEAN") || type_.is_text() || type_.is_enum()
+}
+
+fn is_readable(type_: &Symbol) -> bool {
+    type_.is_numeric() || type_.is_text()
+}
+
+fn has_bounds(type_: &Symbol) -> bool {
+    type_.is_ordinal()
+        || type_.has_dynamic_bounds()
+        || matches!(type_.definition(), Symbol::Array(..))
+}
+
+fn has_length(type_: &Symbol) -> bool {
+    type_.is_text() || type_.has_dynamic_bounds()
+}
+
+/// Whether the builtin `name` accepts arguments with a field width
+pub fn takes_formats(name: &str) -> bool {
+    matches!(name, "write" | "writeln" | "str")
+}
+
+/// The parameters of the builtin `name` when it is called with `count`
+/// arguments
+pub fn formals(name: &str, count: usize) -> Vec<Formal> {
+    use Formal::{Type, Value, Var};
+
+    match name {
+        "length" => vec![Value(has_length)],
+        "uppercase" | "lowercase" | "strtoint" => vec![Value(Symbol::is_text)],
+        "copy" => vec![Value(Symbol::is_text), Value(is_integer), Value(is_integer)],
+        "pos" => vec![Value(Symbol::is_text); 2],
+        "concat" => vec![Value(Symbol::is_text); count.max(1)],
+        "insert" => vec![
+            Value(Symbol::is_text),
+            Var(Symbol::is_string),
+            Value(is_integer),
+        ],
+        "delete" => vec![Var(Symbol::is_string), Value(is_integer), Value(is_integer)],
+        "inttostr" => vec![Value(is_integer)],
+        "val" => vec![
+            Value(Symbol::is_text),
+            Var(Symbol::is_numeric),
+            Var(is_integer),
+        ],
+        "str" => vec![Value(Symbol::is_numeric), Var(Symbol::is_string)],
+        "ord" | "succ" | "pred" => vec![Value(Symbol::is_ordinal)],
+        "chr" => vec![Value(is_integer)],
+        "write" | "writeln" => vec![Value(is_writable); count],
+        "read" | "readln" => vec![Var(is_readable); count],
+        "abs" | "sqr" | "sqrt" | "sin" | "cos" | "arctan" | "exp" | "ln" | "round" | "trunc"
+        | "floor" | "ceil" => vec![Value(Symbol::is_numeric)],
+        "odd" => vec![Value(is_integer)],
+        "pi" => vec![],
+        "power" => vec![Value(Symbol::is_numeric); 2],
+        "inc" | "dec" if count == 2 => vec![Var(Symbol::is_ordinal), Value(is_integer)],
+        "inc" | "dec" => vec![Var(Symbol::is_ordinal)],
+        "low" | "high" => vec![Type(has_bounds)],
+        "sizeof" => vec![Type(|_| true)],
+        "setlength" => vec![Var(Symbol::is_dynamic_array), Value(is_integer)],
+        "new" => vec![Var(Symbol::is_pointer)],
+        "dispose" => vec![Value(Symbol::is_pointer)],
+        _ => unreachable!(),
+    }
+}
+
+/// The type the builtin function `name` returns when called with arguments
+/// of the given types, or None for procedures
+pub fn return_type(name: &str, args: &[Symbol]) -> Option<Symbol> {
+    match name {
+        "length" | "pos" | "strtoint" | "ord" | "round" | "trunc" | "floor" | "ceil" | "sizeof" => {
+            Some(Symbol::builtin("INTEGER"))
+        }
+        "sqrt" | "sin" | "cos" | "arctan" | "exp" | "ln" | "pi" | "power" => {
+            Some(Symbol::builtin("REAL"))
+        }
+        "odd" => Some(Symbol::builtin("BOOLEAN")),
+        "abs" | "sqr" => Some(args[0].base().clone()),
+        "chr" => Some(Symbol::builtin("CHAR")),
+        "succ" | "pred" => Some(args[0].base().clone()),
+        "low" | "high" => match args[0].definition() {
+            Symbol::Array(index, _) => Some(index.base().clone()),
+            Symbol::Conformant(index, _) => Some(index.base().clone()),
+            Symbol::DynArray(_) | Symbol::OpenArray(_) => Some(Symbol::builtin("INTEGER")),
+            ordinal => Some(ordinal.base().clone()),
+        },
+        "copy" | "concat" | "uppercase" | "lowercase" | "inttostr" => {
+            Some(Symbol::builtin("STRING"))
+        }
+        _ => None,
+    }
+}
+
+/// Evaluates a builtin function whose parameters are all value parameters
+pub fn call(name: &str, args: &[Value]) -> Value {
+    match (name, args) {
+        ("length", [Value::DynArray(elements)]) => Value::Integer(elements.borrow().len() as i32),
+        ("length", [Value::Array(_, elements)]) => Value::Integer(elements.len() as i32),
+        ("length", [s]) => Value::Integer(s.expect_string().chars().count() as i32),
+        ("copy", [s, Value::Integer(index), Value::Integer(count)]) => {
+            Value::String(copy(&s.expect_string(), *index, *count))
+        }
+        ("pos", [sub, s]) => Value::Integer(pos(&sub.expect_string(), &s.expect_string())),
+        ("concat", _) => Value::String(args.iter().map(Value::expect_string).collect()),
+        ("uppercase", [s]) => Value::String(s.expect_string().to_uppercase()),
+        ("lowercase", [s]) => Value::String(s.expect_string().to_lowercase()),
+        ("inttostr", [Value::Integer(i)]) => Value::String(i.to_string()),
+        ("ord", [value]) => Value::Integer(value.expect_ordinal()),
+        ("chr", [Value::Integer(code @ 0..=255)]) => {
+            Value::Char(char::from_u32(*code as u32).unwrap())
+        }
+        ("chr", [Value::Integer(code)]) => {
+            RuntimeError::new(format!("Chr({}) is out of range", code)).throw();
+            unreachable!()
+        }
+        ("succ", [value]) => step(name, value, 1),
+        ("pred", [value]) => step(name, value, -1),
+        ("abs", [Value::Integer(i)]) => Value::Integer(checked(i.checked_abs())),
+        ("sqr", [Value::Integer(i)]) => Value::Integer(checked(i.checked_mul(*i))),
+        ("abs", [Value::Float(x)]) => Value::Float(x.abs()),
+        ("sqr", [Value::Float(x)]) => real(name, args, x * x),
+        ("sqrt", [x]) => real(name, args, x.expect_number().sqrt()),
+        ("sin", [x]) => real(name, args, x.expect_number().sin()),
+        ("cos", [x]) => real(name, args, x.expect_number().cos()),
+        ("arctan", [x]) => real(name, args, x.expect_number().atan()),
+        ("exp", [x]) => real(name, args, x.expect_number().exp()),
+        ("ln", [x]) => real(name, args, x.expect_number().ln()),
+        // Rounding goes away from zero for halves, as ISO 7185 requires
+        ("round", [x]) => integer(name, args, x.expect_number().round()),
+        ("trunc", [x]) => integer(name, args, x.expect_number().trunc()),
+        ("floor", [x]) => integer(name, args, x.expect_number().floor()),
+        ("ceil", [x]) => integer(name, args, x.expect_number().ceil()),
+        ("odd", [Value::Integer(i)]) => Value::Boolean(i % 2 != 0),
+        ("pi", []) => Value::Float(PI),
+        ("power", [base, exponent]) => real(
+            name,
+            args,
+            base.expect_number().powf(exponent.expect_number()),
+        ),
+        ("strtoint", [s]) => match s.expect_string().trim().parse() {
+            Ok(i) => Value::Integer(i),
+            Err(_) => {
+                RuntimeError::new(format!("'{}' is not a valid integer", s)).throw();
+                unreachable!()
+            }
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Moves an ordinal value `delta` places along its type, as Succ, Pred, Inc
+/// and Dec do. Going past either end of the type is an error.
+pub fn step(name: &str, value: &Value, delta: i64) -> Value {
+    let (low, high) = match value {
+        Value::Boolean(_) => (0, 1),
+        Value::Char(_) => (0, 255),
+        Value::Enum(_, names) => (0, names.len() as i32 - 1),
+        _ => (i32::MIN, i32::MAX),
+    };
+    let ordinal = value.expect_ordinal() as i64 + delta;
+    if ordinal < low as i64 || ordinal > high as i64 {
+        RuntimeError::new(format!(
+            "Range check error in {}({})",
+            name.to_uppercase(),
+            value
+        ))
+        .throw();
+    }
+    value.with_ordinal(ordinal as i32)
+}
+
+/// Evaluates Low, High or SizeOf, which only depend on the type of their
+/// argument. Low and High of an array give the bounds of its index.
+pub fn inquire(name: &str, type_: &Symbol) -> Value {
+    let ordinal = match type_.definition() {
+        Symbol::Array(index, _) => index,
+        _ => type_,
+    };
+    let (low, high) = ordinal.bounds().unwrap_or((i32::MIN, i32::MAX));
+    match name {
+        "low" => ordinal_value(ordinal, low),
+        "high" => ordinal_value(ordinal, high),
+        "sizeof" => Value::Integer(size_of(type_)),
+        _ => unreachable!(),
+    }
+}
+
+/// The value of an ordinal type with the given ordinal number
+pub fn ordinal_value(type_: &Symbol, ordinal: i32) -> Value {
+    if let Symbol::Enum(names) = type_.base().definition() {
+        Value::Enum(ordinal, names.clone())
+    } else if type_.is_char() {
+        Value::Char(char::from_u32(ordinal as u32).unwrap())
+    } else if type_.is_builtin("BOOLEAN") {
+        Value::Boolean(ordinal != 0)
+    } else {
+        Value::Integer(ordinal)
+    }
+}
+
+/// The size in bytes that SizeOf reports for a type. It follows the layout
+/// Free Pascal would use, so that it doesn't change with how the
+/// interpreter happens to store values.
+fn size_of(type_: &Symbol) -> i32 {
+    match type_.definition() {
+        // Like Free Pascal, a subrange takes the fewest bytes that hold it
+        Symbol::Subrange(_, low, high) => match (low, high) {
+            (-128..=127, -128..=127) | (0..=255, 0..=255) => 1,
+            (-32768..=32767, -32768..=32767) | (0..=65535, 0..=65535) => 2,
+            _ => 4,
+        },
+        Symbol::Array(index, element) => {
+            let (low, high) = index.bounds().unwrap();
+            (high - low + 1) * size_of(element)
+        }
+        Symbol::Record(fields) => fields_size(fields, &[]),
+        // The length byte comes first
+        Symbol::String(length) => length + 1,
+        // A bit for each element, in as many bytes as Free Pascal uses
+        Symbol::Set(Some(element)) if element.bounds().unwrap().1 < 32 => 4,
+        Symbol::Set(_) => 32,
+        _ if type_.is_string() => 256,
+        _ if type_.is_builtin("BOOLEAN") || type_.is_char() => 1,
+        // REAL is single precision here
+        _ => 4,
+    }
+}
+
+/// The size of the fields selected by `guards`: those guarded by nothing
+/// more, followed by the largest of the variants nested inside them
+fn fields_size(fields: &[FieldSymbol], guards: &[Guard]) -> i32 {
+    let mut size = 0;
+    let mut variants: Vec<&Guard> = Vec::new();
+    for field in fields {
+        if field.guards == guards {
+            size += size_of(&field.type_);
+        } else if field.guards.starts_with(guards) {
+            let variant = &field.guards[guards.len()];
+            if !variants.contains(&variant) {
+                variants.push(variant);
+            }
+        }
+    }
+    let largest = variants
+        .into_iter()
+        .map(|variant| fields_size(fields, &[guards, std::slice::from_ref(variant)].concat()))
+        .max();
+    size + largest.unwrap_or(0)
+}
+
+/// Unwraps the result of an INTEGER operation, which fails on overflow
+pub fn checked(result: Option<i32>) -> i32 {
+    result.unwrap_or_else(|| {
+        RuntimeError::new(String::from("Integer overflow")).throw();
+        unreachable!()
+    })
+}
+
+fn math_error(name: &str, args: &[Value]) {
+    let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+    RuntimeError::new(format!(
+        "Floating point error in {}({})",
+        name.to_uppercase(),
+        args.join(", ")
+    ))
+    .throw();
+}
+
+/// Checks the result of a REAL function, so that arguments outside its
+/// domain, like `SQRT(-1)` or `LN(0)`, are errors instead of NaN or infinity
+fn real(name: &str, args: &[Value], result: f32) -> Value {
+    if !result.is_finite() {
+        math_error(name, args);
+    }
+    Value::Float(result)
+}
+
+fn integer(name: &str, args: &[Value], result: f32) -> Value {
+    if !(result >= i32::MIN as f32 && result < i32::MAX as f32) {
+        math_error(name, args);
+    }
+    Value::Integer(result as i32)
+}
+
+/// Formats a value the way Write does, right-aligned in a field of `width`
+/// characters. REAL values are written in scientific notation unless a
+/// precision asks for fixed-point notation.
+pub fn format(value: &Value, width: Option<i32>, precision: Option<i32>) -> String {
+    let text = match (value, precision) {
+        (Value::Float(x), Some(precision)) => format!("{:.*}", precision.max(0) as usize, x),
+        (Value::Float(x), None) => scientific(*x, width),
+        (value, _) => value.to_string(),
+    };
+    format!("{:>1$}", text, width.unwrap_or(0).max(0) as usize)
+}
+
+/// Writes a REAL like ` 1.500000000E+00`, with as many digits as fit into
+/// the field width. A REAL holds only about 7 digits, so the ones after the
+/// shortest text that reads back as the same value are written as zeros
+/// rather than as the noise of its binary fraction.
+fn scientific(x: f32, width: Option<i32>) -> String {
+    // The sign, the first digit, the point and the exponent take 7 characters
+    let digits = width.map_or(9, |width| (width - 7).max(1) as usize);
+    let shortest = format!("{:E}", x.abs());
+    let (mantissa, _) = shortest.split_once('E').unwrap();
+    let (first, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let text = if fraction.len() < digits {
+        format!(
+            "{}.{:0<3$}{}",
+            first,
+            fraction,
+            &shortest[mantissa.len()..],
+            digits
+        )
+    } else {
+        format!("{:.*E}", digits, x.abs())
+    };
+    let (mantissa, exponent) = text.split_once('E').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    format!(
+        "{}{}E{}{:02}",
+        if x < 0.0 { '-' } else { ' ' },
+        mantissa,
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
+
+/// Strings are indexed from 1, and out of range arguments are clamped the
+/// way Free Pascal does it rather than being errors
+fn copy(s: &str, index: i32, count: i32) -> String {
+    let start = (index.max(1) - 1) as usize;
+    s.chars().skip(start).take(count.max(0) as usize).collect()
+}
+
+fn pos(sub: &str, s: &str) -> i32 {
+    if sub.is_empty() {
+        return 0;
+    }
+    s.find(sub)
+        .map_or(0, |offset| s[..offset].chars().count() as i32 + 1)
+}
+
+pub fn insert(source: &str, s: &str, index: i32) -> String {
+    let mut chars: Vec<char> = s.chars().collect();
+    let index = ((index.max(1) - 1) as usize).min(chars.len());
+    chars.splice(index..index, source.chars());
+    chars.into_iter().collect()
+}
+
+pub fn delete(s: &str, index: i32, count: i32) -> String {
+    let mut chars: Vec<char> = s.chars().collect();
+    if index < 1 || index as usize > chars.len() || count <= 0 {
+        return s.to_string();
+    }
+    let start = index as usize - 1;
+    let end = (start + count as usize).min(chars.len());
+    chars.drain(start..end);
+    chars.into_iter().collect()
+}
+
+/// Parses a number like Val does. On failure this gives the position of
+/// the first character that doesn't belong to the number.
+pub fn val(s: &str, real: bool) -> Result<Value, i32> {
+    let parse = |text: &str| {
+        if real {
+            // Rust would also accept words like `inf`
+            let numeric = text
+                .chars()
+                .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+            text.parse().ok().filter(|_| numeric).map(Value::Float)
+        } else {
+            text.parse().ok().map(Value::Integer)
+        }
+    };
+
+    let trimmed = s.trim_start();
+    if let Some(value) = parse(trimmed) {
+        return Ok(value);
+    }
+    let skipped = s.chars().count() - trimmed.chars().count();
+    let chars: Vec<char> = trimmed.chars().collect();
+    let valid = (0..chars.len())
+        .rev()
+        .find(|&end| parse(&chars[..end].iter().collect::<String>()).is_some())
+        .unwrap_or(0);
+    Err((skipped + valid + 1) as i32)
+}
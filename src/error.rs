@@ -6,6 +6,21 @@ pub enum ErrorCode {
     IDNotFound,
     DuplicateID,
     WrongParamsNum,
+    TypeMismatch,
+    InvalidControlVariable,
+    ControlVariableAssigned,
+    ConstantAssigned,
+    ConstantExpected,
+    DuplicateCaseLabel,
+    VariableExpected,
+    InvalidRange,
+    InvalidStringLength,
+    UnexpectedFormat,
+    ForwardNotSolved,
+    HeadingMismatch,
+    ResultTypeExpected,
+    NestedRoutineValue,
+    TurboOnly,
 }
 
 impl Display for ErrorCode {
@@ -15,6 +30,27 @@ impl Display for ErrorCode {
             ErrorCode::UnexpectedToken => write!(f, "Unexpected token"),
             ErrorCode::IDNotFound => write!(f, "Identifier not found"),
             ErrorCode::WrongParamsNum => write!(f, "Wrong number of params"),
+            ErrorCode::TypeMismatch => write!(f, "Type mismatch"),
+            ErrorCode::InvalidControlVariable => write!(f, "Invalid FOR control variable"),
+            ErrorCode::ControlVariableAssigned => {
+                write!(f, "Assignment to FOR control variable")
+            }
+            ErrorCode::ConstantAssigned => write!(f, "Assignment to constant"),
+            ErrorCode::ConstantExpected => write!(f, "Constant expression expected"),
+            ErrorCode::DuplicateCaseLabel => write!(f, "Duplicate case label"),
+            ErrorCode::VariableExpected => write!(f, "Variable expected"),
+            ErrorCode::InvalidRange => write!(f, "Lower bound exceeds upper bound"),
+            ErrorCode::InvalidStringLength => write!(f, "Invalid string length"),
+            ErrorCode::UnexpectedFormat => write!(f, "Field width not allowed here"),
+            ErrorCode::ForwardNotSolved => write!(f, "Forward declaration not solved"),
+            ErrorCode::HeadingMismatch => {
+                write!(f, "Heading doesn't match the forward declaration")
+            }
+            ErrorCode::ResultTypeExpected => write!(f, "Function result type expected"),
+            ErrorCode::TurboOnly => write!(f, "Only allowed in Turbo Pascal mode"),
+            ErrorCode::NestedRoutineValue => {
+                write!(f, "Nested routine can't be kept in a procedural variable")
+            }
         }
     }
 }
@@ -73,3 +109,17 @@ impl SemanticError {
         panic!("Semantic Error: {}", self.message)
     }
 }
+
+pub struct RuntimeError {
+    message: String,
+}
+
+impl RuntimeError {
+    pub fn new(message: String) -> Self {
+        RuntimeError { message }
+    }
+
+    pub fn throw(self) {
+        panic!("Runtime Error: {}", self.message)
+    }
+}
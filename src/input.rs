@@ -0,0 +1,114 @@
+use crate::error::RuntimeError;
+use std::io::BufRead;
+
+/// Text input read the way Pascal's Read and ReadLn see it: line by line,
+/// with the end of each line visible as its own position
+pub struct Input {
+    reader: Box<dyn BufRead>,
+    line: Option<Vec<char>>,
+    pos: usize,
+}
+
+impl Input {
+    pub fn new(reader: Box<dyn BufRead>) -> Self {
+        Input {
+            reader,
+            line: None,
+            pos: 0,
+        }
+    }
+
+    /// Makes sure a line is available, returning false at the end of input
+    fn fill(&mut self) -> bool {
+        if self.line.is_none() {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return false,
+                Ok(_) => {}
+                Err(e) => RuntimeError::new(format!("Cannot read input: {}", e)).throw(),
+            }
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            self.line = Some(line.chars().collect());
+            self.pos = 0;
+        }
+        true
+    }
+
+    /// The next character, where the end of a line reads as '\n'
+    fn peek(&mut self) -> Option<char> {
+        if !self.fill() {
+            return None;
+        }
+        let line = self.line.as_ref().unwrap();
+        Some(line.get(self.pos).copied().unwrap_or('\n'))
+    }
+
+    fn advance(&mut self) {
+        let length = self.line.as_ref().map_or(0, Vec::len);
+        if self.pos < length {
+            self.pos += 1;
+        } else {
+            self.line = None;
+        }
+    }
+
+    fn past_end(&self) {
+        RuntimeError::new(String::from("Read past end of input")).throw();
+    }
+
+    /// Reads a character. The end of a line reads as a space.
+    pub fn read_char(&mut self) -> char {
+        match self.peek() {
+            Some(c) => {
+                self.advance();
+                if c == '\n' {
+                    ' '
+                } else {
+                    c
+                }
+            }
+            None => {
+                self.past_end();
+                unreachable!()
+            }
+        }
+    }
+
+    /// Reads the rest of the current line, leaving the end of the line to
+    /// be skipped by ReadLn
+    pub fn read_string(&mut self) -> String {
+        if !self.fill() {
+            self.past_end();
+        }
+        let line = self.line.as_ref().unwrap();
+        let rest = line[self.pos.min(line.len())..].iter().collect();
+        self.pos = line.len();
+        rest
+    }
+
+    /// Skips blanks and line ends, then reads the characters of a number up
+    /// to the next blank or line end
+    pub fn read_word(&mut self) -> String {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.advance();
+        }
+        if self.peek().is_none() {
+            self.past_end();
+        }
+
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|c| !c.is_whitespace()) {
+            word.push(c);
+            self.advance();
+        }
+        word
+    }
+
+    /// Moves to the start of the next line, as ReadLn does after reading
+    pub fn skip_line(&mut self) {
+        if self.fill() {
+            self.line = None;
+        }
+    }
+}
@@ -1,23 +1,44 @@
 use crate::ast::*;
+use crate::builtins;
+use crate::error::RuntimeError;
 // use crate::parser::Parser;
-use crate::symbols::{ARType, ActivationRecord, CallStack};
-use crate::tokens::{TokenType, Value};
+use crate::input::Input;
+use crate::symbols::{
+    ARType, ActivationRecord, CallStack, Component, Guard, Heap, Reference, Symbol,
+};
+use crate::tokens::{Token, TokenType, Value};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
 
 pub trait NodeVisitor {
-    fn visit_num(&mut self, num: &mut Node) -> Value;
-    fn visit_bin_op(&mut self, bin_op: &mut Node) -> Value;
-    fn visit_unary_op(&mut self, unary_op: &mut Node) -> Value;
-    fn visit_compound(&mut self, compound: &mut Compound) -> Value;
-    fn visit_assign(&mut self, assign: &mut Node) -> Value;
-    fn visit_var(&mut self, var: &mut Var) -> Value;
-    fn visit_program(&mut self, program: &mut Node) -> Value;
-    fn visit_block(&mut self, block: &mut Block) -> Value;
-    fn visit_var_decl(&mut self, var_decl: &mut Node) -> Value;
-    fn visit_type(&mut self, type_: &mut Type) -> Value;
-    fn visit_procedure_decl(&mut self, procedure_decl: &mut Node) -> Value;
-    fn visit_procedure_call(&mut self, procedure_call: &mut ProcedureCall) -> Value;
-
-    fn visit(&mut self, node: &mut Node) -> Value {
+    type Output: Default;
+
+    fn visit_num(&mut self, num: &mut Node) -> Self::Output;
+    fn visit_bin_op(&mut self, bin_op: &mut Node) -> Self::Output;
+    fn visit_unary_op(&mut self, unary_op: &mut Node) -> Self::Output;
+    fn visit_compound(&mut self, compound: &mut Compound) -> Self::Output;
+    fn visit_assign(&mut self, assign: &mut Node) -> Self::Output;
+    fn visit_var(&mut self, var: &mut Var) -> Self::Output;
+    fn visit_program(&mut self, program: &mut Node) -> Self::Output;
+    fn visit_block(&mut self, block: &mut Block) -> Self::Output;
+    fn visit_var_decl(&mut self, var_decl: &mut Node) -> Self::Output;
+    fn visit_const_decl(&mut self, const_decl: &mut Node) -> Self::Output;
+    fn visit_type_decl(&mut self, type_decl: &mut Node) -> Self::Output;
+    fn visit_type(&mut self, type_: &mut Type) -> Self::Output;
+    fn visit_procedure_decl(&mut self, procedure_decl: &mut Node) -> Self::Output;
+    fn visit_procedure_call(&mut self, procedure_call: &mut ProcedureCall) -> Self::Output;
+    fn visit_if(&mut self, if_: &mut Node) -> Self::Output;
+    fn visit_while(&mut self, while_: &mut Node) -> Self::Output;
+    fn visit_repeat(&mut self, repeat: &mut Node) -> Self::Output;
+    fn visit_for(&mut self, for_: &mut Node) -> Self::Output;
+    fn visit_case(&mut self, case: &mut Node) -> Self::Output;
+    fn visit_set(&mut self, set: &mut Node) -> Self::Output;
+    fn visit_formatted(&mut self, formatted: &mut Node) -> Self::Output;
+
+    fn visit(&mut self, node: &mut Node) -> Self::Output {
         match node {
             Node::BinOp(..) => self.visit_bin_op(node),
             Node::UnaryOp(..) => self.visit_unary_op(node),
@@ -27,27 +48,481 @@ pub trait NodeVisitor {
             Node::Var(n) => self.visit_var(n),
             Node::Program(..) => self.visit_program(node),
             Node::VarDecl(..) => self.visit_var_decl(node),
+            Node::ConstDecl(..) => self.visit_const_decl(node),
+            Node::TypeDecl(..) => self.visit_type_decl(node),
             Node::ProcedureDecl(..) => self.visit_procedure_decl(node),
             Node::ProcedureCall(n) => self.visit_procedure_call(n),
+            Node::If(..) => self.visit_if(node),
+            Node::While(..) => self.visit_while(node),
+            Node::Repeat(..) => self.visit_repeat(node),
+            Node::For(..) => self.visit_for(node),
+            Node::Case(..) => self.visit_case(node),
+            Node::Set(..) => self.visit_set(node),
+            Node::Formatted(..) => self.visit_formatted(node),
             // Node::Block(n) => self.visit_block(n),
-            Node::NoOp => Value::None,
+            Node::NoOp => Self::Output::default(),
         }
     }
 }
 
 pub struct Interpreter {
     call_stack: CallStack,
+    heap: Heap,
+    input: Input,
+    output: Box<dyn Write>,
+    /// Whether values stored into subrange variables are checked
+    range_checks: bool,
+    /// Whether misused pointers are reported where they are used, and heap
+    /// variables left over at exit are listed
+    heap_checks: bool,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Interpreter::with_io(Box::new(io::stdin().lock()), Box::new(io::stdout()))
+    }
+
+    /// An interpreter whose programs read from `input` and write to `output`
+    pub fn with_io(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
         Interpreter {
             call_stack: CallStack::new(),
+            heap: Heap::new(),
+            input: Input::new(input),
+            output,
+            range_checks: false,
+            heap_checks: false,
+        }
+    }
+
+    /// Makes storing a value outside of a subrange an error, like Free
+    /// Pascal's `-Cr`
+    pub fn enable_range_checks(&mut self) {
+        self.range_checks = true;
+    }
+
+    /// Reports the misuse of pointers along with where it happens, and the
+    /// heap variables a program never disposes of, like Free Pascal's
+    /// heaptrc unit
+    pub fn enable_heap_checks(&mut self) {
+        self.heap_checks = true;
+    }
+
+    /// Checks a value about to be stored into a variable of the given type
+    fn check_range(&self, value: &Value, type_: &Option<Symbol>) {
+        if !self.range_checks {
+            return;
+        }
+        if let Some(Symbol::Subrange(_, low, high)) = type_.as_ref().map(Symbol::definition) {
+            let ordinal = value.expect_ordinal();
+            if ordinal < *low || ordinal > *high {
+                RuntimeError::new(format!(
+                    "Value {} out of range {}..{}",
+                    value,
+                    value.with_ordinal(*low),
+                    value.with_ordinal(*high)
+                ))
+                .throw();
+            }
+        }
+    }
+
+    /// Evaluates an expression that only involves constants, as the
+    /// analyzer does for constant expressions
+    pub fn evaluate(node: &Node) -> Value {
+        let mut interpreter = Interpreter::with_io(Box::new(io::empty()), Box::new(io::sink()));
+        interpreter.call_stack.push(ActivationRecord::new(
+            String::from("constant"),
+            ARType::Program,
+            0,
+        ));
+        interpreter.visit(&mut node.clone())
+    }
+
+    /// Finds the slot a variable name stands for by walking the static
+    /// chain outwards, following VAR parameters to the record they were
+    /// passed from
+    fn resolve(&self, name: &str) -> Reference {
+        let name = name.to_lowercase();
+        let mut record = Some(self.call_stack.len() - 1);
+        while let Some(index) = record {
+            let ar = self.call_stack.get(index);
+            if let Some(reference) = ar.get_reference(&name) {
+                return reference.clone();
+            }
+            if ar.get(name.clone()).is_some() {
+                return Reference::new(index, name);
+            }
+            record = ar.access_link;
+        }
+
+        // Undeclared variables live in the running block
+        Reference::new(self.call_stack.len() - 1, name)
+    }
+
+    /// Finds a procedure or function along the static chain, along with the
+    /// record of the block that declares it. Builtins aren't found here.
+    fn resolve_routine(&self, name: &str) -> Option<(usize, ProcedureDecl)> {
+        let name = name.to_lowercase();
+        let mut record = Some(self.call_stack.len() - 1);
+        while let Some(index) = record {
+            let ar = self.call_stack.get(index);
+            if let Some(routine) = ar.get_routine(&name) {
+                return Some((index, routine.clone()));
+            }
+            // A variable of a procedural type holds the routine along with
+            // the record it runs in
+            let value = match ar.get_reference(&name) {
+                Some(reference) => Some(self.load(reference)),
+                None => ar.get(name.clone()).cloned(),
+            };
+            if let Some(value) = value {
+                return match value {
+                    Value::Routine(env, routine) => Some((
+                        env,
+                        self.call_stack
+                            .get(env)
+                            .get_routine(&routine)
+                            .unwrap()
+                            .clone(),
+                    )),
+                    _ => {
+                        RuntimeError::new(format!("Procedural variable {} not assigned", name))
+                            .throw();
+                        unreachable!()
+                    }
+                };
+            }
+            record = ar.access_link;
+        }
+        None
+    }
+
+    /// Finds the record of the running function `name` along the static
+    /// chain, whose result an assignment to that name sets. Nested routines
+    /// can set the result of a function they are declared in.
+    fn function_record(&self, name: &str) -> Option<usize> {
+        let mut record = Some(self.call_stack.len() - 1);
+        while let Some(index) = record {
+            let ar = self.call_stack.get(index);
+            if ar.get(name.to_string()).is_some() || ar.get_reference(name).is_some() {
+                return None;
+            }
+            if ar.is_function(name) {
+                return Some(index);
+            }
+            record = ar.access_link;
+        }
+        None
+    }
+
+    /// Resolves a variable and evaluates its selectors, giving the exact
+    /// component the designator stands for
+    fn reference(&mut self, var: &mut Var) -> Reference {
+        let mut reference = self.resolve(&var.value.expect_string());
+        for selector in &mut var.selectors {
+            match selector {
+                Selector::Index(index) => {
+                    let index = self.visit(index).expect_ordinal();
+                    reference.path.push(Component::Index(index));
+                }
+                Selector::Position(index) => {
+                    let index = self.visit(index).expect_ordinal();
+                    reference.path.push(Component::Position(index));
+                }
+                Selector::Field(name, guards) => reference
+                    .path
+                    .push(Component::Field(name.value.expect_string(), guards.clone())),
+                // The rest of the path is taken from the variable pointed to
+                Selector::Deref(token) => {
+                    let name = format!("{}^", reference.name);
+                    let address = self.follow(&self.read(&reference), &name, token);
+                    reference = Reference::heap(address, name, token.clone());
+                }
+            }
+        }
+        reference
+    }
+
+    /// Gives the address a pointer points to, which has to be a heap
+    /// variable that is still there
+    fn follow(&self, pointer: &Value, name: &str, token: &Token) -> usize {
+        let message = match pointer {
+            Value::Pointer(Some(address)) if self.heap.get(*address).is_some() => return *address,
+            _ if !self.heap_checks => format!("Invalid pointer in {}", name),
+            Value::Pointer(None) => format!(
+                "Dereference of NIL pointer in {} at {}",
+                name,
+                token.position()
+            ),
+            _ => format!(
+                "Dereference of disposed pointer in {} at {}",
+                name,
+                token.position()
+            ),
+        };
+        RuntimeError::new(message).throw();
+        unreachable!()
+    }
+
+    /// Lists the heap variables that were never disposed of
+    fn report_leaks(&mut self) {
+        let leaks = self.heap.leaks();
+        if leaks.is_empty() {
+            return;
+        }
+        let mut text = format!("{} heap variable(s) not disposed:\n", leaks.len());
+        for (address, origin) in leaks {
+            text.push_str(&format!(
+                "  ^{} created by {} at {}\n",
+                address,
+                origin.value,
+                origin.position()
+            ));
+        }
+        self.write(&text);
+    }
+
+    fn write(&mut self, text: &str) {
+        if let Err(e) = self
+            .output
+            .write_all(text.as_bytes())
+            .and_then(|_| self.output.flush())
+        {
+            RuntimeError::new(format!("Cannot write output: {}", e)).throw();
+        }
+    }
+
+    /// Evaluates an argument passed to a VAR parameter of a builtin, giving
+    /// the variable's type along with the reference
+    fn variable(&mut self, node: &mut Node) -> (Reference, Option<Symbol>) {
+        match node {
+            Node::Var(var) => (self.reference(var), var.type_symbol.clone()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reads a variable, which is an error while it is undefined
+    fn read(&self, reference: &Reference) -> Value {
+        match self.load(reference) {
+            Value::None => {
+                RuntimeError::new(format!("Variable {} is undefined", reference.name)).throw();
+                unreachable!()
+            }
+            value => value,
+        }
+    }
+
+    fn call_builtin(&mut self, procedure_call: &mut ProcedureCall) -> Value {
+        let name = procedure_call.proc_name.to_lowercase();
+        let args = &mut procedure_call.actual_params;
+        match name.as_str() {
+            "insert" => {
+                let source = self.visit(&mut args[0]).expect_string();
+                let (target, type_) = self.variable(&mut args[1]);
+                let index = self.visit(&mut args[2]).expect_ordinal();
+                let s = self.read(&target).expect_string();
+                let result = Value::String(builtins::insert(&source, &s, index));
+                self.store(&target, coerce(result, &type_));
+            }
+            "delete" => {
+                let (target, _) = self.variable(&mut args[0]);
+                let index = self.visit(&mut args[1]).expect_ordinal();
+                let count = self.visit(&mut args[2]).expect_ordinal();
+                let s = self.read(&target).expect_string();
+                self.store(&target, Value::String(builtins::delete(&s, index, count)));
+            }
+            "val" => {
+                let s = self.visit(&mut args[0]).expect_string();
+                let (number, type_) = self.variable(&mut args[1]);
+                let (code, _) = self.variable(&mut args[2]);
+                let real = type_.is_some_and(|type_| type_.is_builtin("REAL"));
+                match builtins::val(&s, real) {
+                    Ok(value) => {
+                        self.store(&number, value);
+                        self.store(&code, Value::Integer(0));
+                    }
+                    Err(position) => self.store(&code, Value::Integer(position)),
+                }
+            }
+            "str" => {
+                let text = builtins::format(&self.visit(&mut args[0]), None, None);
+                let (target, type_) = self.variable(&mut args[1]);
+                self.store(&target, coerce(Value::String(text), &type_));
+            }
+            "write" | "writeln" => {
+                let mut text = String::new();
+                for arg in args.iter_mut() {
+                    text.push_str(&builtins::format(&self.visit(arg), None, None));
+                }
+                if name == "writeln" {
+                    text.push('\n');
+                }
+                self.write(&text);
+            }
+            "inc" | "dec" => {
+                let (target, type_) = self.variable(&mut args[0]);
+                let delta = match args.get_mut(1) {
+                    Some(delta) => self.visit(delta).expect_ordinal() as i64,
+                    None => 1,
+                };
+                let delta = if name == "dec" { -delta } else { delta };
+                let value = builtins::step(&name, &self.read(&target), delta);
+                self.check_range(&value, &type_);
+                self.store(&target, value);
+            }
+            "new" => {
+                let (target, type_) = self.variable(&mut args[0]);
+                let value = match type_.as_ref().map(Symbol::definition) {
+                    Some(Symbol::Pointer(Some(target))) => default_value(&target.type_()),
+                    _ => unreachable!(),
+                };
+                let address = self.heap.allocate(value, procedure_call.token.clone());
+                self.store(&target, Value::Pointer(Some(address)));
+            }
+            "dispose" => {
+                let token = &procedure_call.token;
+                let message = match self.visit(&mut args[0]) {
+                    Value::Pointer(Some(address)) if self.heap.dispose(address).is_some() => {
+                        return Value::None
+                    }
+                    _ if !self.heap_checks => String::from("Invalid pointer operation"),
+                    Value::Pointer(None) => {
+                        format!("Dispose of NIL pointer at {}", token.position())
+                    }
+                    _ => format!("Pointer disposed twice at {}", token.position()),
+                };
+                RuntimeError::new(message).throw();
+            }
+            "setlength" => {
+                let (target, type_) = self.variable(&mut args[0]);
+                let length = self.visit(&mut args[1]).expect_ordinal();
+                if length < 0 {
+                    RuntimeError::new(format!("Invalid array length {}", length)).throw();
+                }
+                let element = match type_.as_ref().map(Symbol::definition) {
+                    Some(Symbol::DynArray(element)) => zero_value(element),
+                    _ => unreachable!(),
+                };
+                // The resized array is a copy, so other variables sharing the
+                // old one don't see it change
+                let mut elements = match self.read(&target) {
+                    Value::DynArray(elements) => elements.borrow().clone(),
+                    _ => unreachable!(),
+                };
+                elements.resize(length as usize, element);
+                self.store(&target, Value::DynArray(Rc::new(RefCell::new(elements))));
+            }
+            "low" | "high" | "sizeof" => match &mut args[0] {
+                // Some arrays only know their bounds at run time
+                Node::Var(var)
+                    if name != "sizeof"
+                        && var
+                            .type_symbol
+                            .as_ref()
+                            .is_some_and(Symbol::has_dynamic_bounds) =>
+                {
+                    let type_ = var.type_symbol.clone().unwrap();
+                    let (low, length) = match self.visit_var(var) {
+                        Value::Array(low, elements) => (low, elements.len() as i32),
+                        Value::DynArray(elements) => (0, elements.borrow().len() as i32),
+                        _ => unreachable!(),
+                    };
+                    return match type_.definition() {
+                        // Conformant arrays keep the bounds of the array passed
+                        Symbol::Conformant(index, _) => {
+                            let bound = if name == "low" { low } else { low + length - 1 };
+                            builtins::ordinal_value(index, bound)
+                        }
+                        _ if name == "low" => Value::Integer(0),
+                        _ => Value::Integer(length - 1),
+                    };
+                }
+                Node::Var(var) => {
+                    return builtins::inquire(&name, var.type_symbol.as_ref().unwrap())
+                }
+                _ => unreachable!(),
+            },
+            "read" | "readln" => {
+                for arg in args.iter_mut() {
+                    let (target, var_type) = self.variable(arg);
+                    let type_ = var_type.clone().unwrap();
+                    let value = if type_.is_char() {
+                        Value::Char(self.input.read_char())
+                    } else if type_.is_string() {
+                        Value::String(self.input.read_string())
+                    } else {
+                        let word = self.input.read_word();
+                        match builtins::val(&word, type_.is_builtin("REAL")) {
+                            Ok(value) => value,
+                            Err(_) => {
+                                RuntimeError::new(format!("'{}' is not a valid number", word))
+                                    .throw();
+                                unreachable!()
+                            }
+                        }
+                    };
+                    let value = coerce(value, &Some(type_));
+                    self.check_range(&value, &var_type);
+                    self.store(&target, value);
+                }
+                if name == "readln" {
+                    self.input.skip_line();
+                }
+            }
+            _ => {
+                let values: Vec<_> = args.iter_mut().map(|arg| self.visit(arg)).collect();
+                return builtins::call(&name, &values);
+            }
+        }
+
+        Value::None
+    }
+
+    /// Gives the heap address a reference starts from, checking that the
+    /// variable is still there. A VAR parameter bound to `p^` outlives a
+    /// Dispose(p) in the routine.
+    fn heap_address(&self, reference: &Reference) -> Option<usize> {
+        let (address, token) = reference.address.as_ref()?;
+        Some(self.follow(&Value::Pointer(Some(*address)), &reference.name, token))
+    }
+
+    fn load(&self, reference: &Reference) -> Value {
+        let value = match self.heap_address(reference) {
+            Some(address) => self.heap.get(address),
+            None => self
+                .call_stack
+                .get(reference.record)
+                .get(reference.name.clone()),
+        }
+        .unwrap_or(&Value::None);
+        load_component(value, &reference.path)
+    }
+
+    fn store(&mut self, reference: &Reference, value: Value) {
+        let address = self.heap_address(reference);
+        let ar = self.call_stack.get_mut(reference.record);
+        if reference.path.is_empty() {
+            match address {
+                Some(address) => *self.heap.get_mut(address).unwrap() = value,
+                None => ar.set(reference.name.clone(), value),
+            }
+            return;
+        }
+
+        let slot = match address {
+            Some(address) => self.heap.get_mut(address),
+            None => ar.get_mut(&reference.name),
+        }
+        .unwrap();
+        if !store_component(slot, &reference.path, value) {
+            RuntimeError::new(format!("Variable {} is undefined", reference.name)).throw()
         }
     }
 }
 
 impl NodeVisitor for Interpreter {
+    type Output = Value;
+
     fn visit_num(&mut self, num: &mut Node) -> Value {
         if let Node::Num(value) = num {
             value.clone()
@@ -58,35 +533,81 @@ impl NodeVisitor for Interpreter {
 
     fn visit_bin_op(&mut self, bin_op: &mut Node) -> Value {
         if let Node::BinOp(left, op, right) = bin_op {
-            let mut float = false;
-            let left = match self.visit(left) {
-                Value::Integer(l) => l as f32,
-                Value::Float(l) => {
-                    float = true;
-                    l
-                }
-                _ => panic!(),
-            };
-            let right = match self.visit(right) {
-                Value::Integer(r) => r as f32,
-                Value::Float(r) => {
-                    float = true;
-                    r
+            match op.type_ {
+                TokenType::Equal
+                | TokenType::NotEqual
+                | TokenType::LessThan
+                | TokenType::LessEqual
+                | TokenType::GreaterThan
+                | TokenType::GreaterEqual => {
+                    let left = self.visit(left);
+                    let right = self.visit(right);
+                    if let (Value::Set(l), Value::Set(r)) = (&left, &right) {
+                        return Value::Boolean(match op.type_ {
+                            TokenType::Equal => l == r,
+                            TokenType::NotEqual => l != r,
+                            TokenType::LessEqual => l.is_subset(r),
+                            _ => l.is_superset(r),
+                        });
+                    }
+                    let ordering = compare(&left, &right);
+                    return Value::Boolean(match op.type_ {
+                        TokenType::Equal => ordering == Some(Ordering::Equal),
+                        TokenType::NotEqual => ordering != Some(Ordering::Equal),
+                        TokenType::LessThan => ordering == Some(Ordering::Less),
+                        TokenType::LessEqual => {
+                            matches!(ordering, Some(Ordering::Less | Ordering::Equal))
+                        }
+                        TokenType::GreaterThan => ordering == Some(Ordering::Greater),
+                        _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    });
                 }
-                _ => panic!(),
-            };
+                _ => {}
+            }
 
-            let res = match op.type_ {
-                TokenType::Plus => left + right,
-                TokenType::Minus => left - right,
-                TokenType::Mul => left * right,
-                TokenType::IntegerDiv => (left as i32 / right as i32) as f32,
-                TokenType::FloatDiv => left / right,
-                _ => panic!(),
-            };
-            match float {
-                true => Value::Float(res as f32),
-                false => Value::Integer(res as i32),
+            let left = self.visit(left);
+            // The right operand of a boolean AND or OR is only evaluated
+            // when it decides the result
+            match (&left, &op.type_) {
+                (Value::Boolean(false), TokenType::And) => return Value::Boolean(false),
+                (Value::Boolean(true), TokenType::Or) => return Value::Boolean(true),
+                _ => {}
+            }
+            let right = self.visit(right);
+
+            match (left, right) {
+                // Otherwise the right operand of AND or OR is the result
+                (Value::Boolean(_), Value::Boolean(r)) if op.type_ != TokenType::Xor => {
+                    Value::Boolean(r)
+                }
+                (Value::Boolean(l), Value::Boolean(r)) => Value::Boolean(l != r),
+                (Value::Set(l), Value::Set(r)) => Value::Set(match op.type_ {
+                    TokenType::Plus => &l | &r,
+                    TokenType::Mul => &l & &r,
+                    _ => &l - &r,
+                }),
+                (element, Value::Set(set)) => {
+                    Value::Boolean(set.contains(&element.expect_ordinal()))
+                }
+                (left @ (Value::String(_) | Value::Char(_)), right) => {
+                    Value::String(format!("{}{}", left, right))
+                }
+                (Value::Integer(l), Value::Integer(r)) if op.type_ != TokenType::FloatDiv => {
+                    Value::Integer(integer_operation(&op.type_, l, r))
+                }
+                (left, right) => {
+                    let (l, r) = (left.expect_number(), right.expect_number());
+                    Value::Float(match op.type_ {
+                        TokenType::Plus => l + r,
+                        TokenType::Minus => l - r,
+                        TokenType::Mul => l * r,
+                        _ if r == 0.0 => {
+                            RuntimeError::new(String::from("Division by zero")).throw();
+                            unreachable!()
+                        }
+                        _ => l / r,
+                    })
+                }
             }
         } else {
             unreachable!()
@@ -96,6 +617,10 @@ impl NodeVisitor for Interpreter {
     fn visit_unary_op(&mut self, unary_op: &mut Node) -> Value {
         if let Node::UnaryOp(op, expr) = unary_op {
             match self.visit(expr) {
+                Value::Boolean(b) => match op.type_ {
+                    TokenType::Not => Value::Boolean(!b),
+                    _ => unimplemented!(),
+                },
                 Value::Float(n) => match op.type_ {
                     TokenType::Plus => Value::Float((0.0) + n),
                     TokenType::Minus => Value::Float((0.0) - n),
@@ -103,8 +628,8 @@ impl NodeVisitor for Interpreter {
                 },
                 Value::Integer(n) => match op.type_ {
                     TokenType::Plus => Value::Integer(n),
-                    TokenType::Minus => Value::Integer(0 - n),
-                    _ => unimplemented!(),
+                    TokenType::Minus => Value::Integer(builtins::checked(n.checked_neg())),
+                    _ => Value::Integer(!n),
                 },
                 _ => panic!("Error"),
             }
@@ -123,29 +648,45 @@ impl NodeVisitor for Interpreter {
 
     fn visit_assign(&mut self, assign: &mut Node) -> Value {
         if let Node::Assign(left, _, right) = assign {
-            let value = self.visit(right);
-            let ar = self.call_stack.peek().unwrap();
-            ar.set(left.value.expect_string(), value);
+            let value = coerce(self.visit(right), &left.type_symbol);
+            self.check_range(&value, &left.type_symbol);
+            let name = left.value.expect_string().to_lowercase();
+            let function = match left.selectors.is_empty() {
+                true => self.function_record(&name),
+                false => None,
+            };
+            if let Some(index) = function {
+                let ar = self.call_stack.get_mut(index);
+                ar.set(String::from(ActivationRecord::RESULT), value);
+            } else {
+                let reference = self.reference(left);
+                self.store(&reference, value);
+            }
         }
 
         Value::None
     }
 
     fn visit_var(&mut self, var: &mut Var) -> Value {
-        let ar = self.call_stack.peek().unwrap();
-        ar.get(var.value.expect_string().to_lowercase())
-            .unwrap()
-            .clone()
+        // A routine passed on as a value keeps the environment it was
+        // declared in
+        if let Some(Symbol::Procedure(_)) = var.type_symbol {
+            let name = var.value.expect_string();
+            let (env, _) = self.resolve_routine(&name).unwrap();
+            return Value::Routine(env, name.to_lowercase());
+        }
+        let reference = self.reference(var);
+        self.read(&reference)
     }
 
     fn visit_program(&mut self, program: &mut Node) -> Value {
         if let Node::Program(name, block) = program {
-            println!("ENTER PROGRAM: {}", &name);
             self.call_stack
                 .push(ActivationRecord::new(name.clone(), ARType::Program, 1));
             self.visit_block(block);
-            println!("{:#?}", self.call_stack);
-            println!("EXIT PROGRAM: {}", &name);
+            if self.heap_checks {
+                self.report_leaks();
+            }
             if let Some(ar) = self.call_stack.peek() {
                 // Keep outermost ar for tests
                 if ar.nesting_level != 1 {
@@ -165,7 +706,36 @@ impl NodeVisitor for Interpreter {
         Value::None
     }
 
-    fn visit_var_decl(&mut self, _: &mut Node) -> Value {
+    fn visit_var_decl(&mut self, var_decl: &mut Node) -> Value {
+        if let Node::VarDecl(var_node, _, initial_value) = var_decl {
+            // Declared but undefined until something is assigned
+            let value = match initial_value {
+                Some(initial_value) => {
+                    let value = coerce(self.visit(initial_value), &var_node.type_symbol);
+                    self.check_range(&value, &var_node.type_symbol);
+                    value
+                }
+                None => var_node
+                    .type_symbol
+                    .as_ref()
+                    .map_or(Value::None, default_value),
+            };
+            self.call_stack
+                .peek()
+                .unwrap()
+                .set(var_node.value.expect_string().to_lowercase(), value);
+        }
+
+        Value::None
+    }
+
+    /// Constants have been replaced by their values, so there is nothing
+    /// left to do at run time
+    fn visit_const_decl(&mut self, _: &mut Node) -> Value {
+        Value::None
+    }
+
+    fn visit_type_decl(&mut self, _: &mut Node) -> Value {
         Value::None
     }
 
@@ -173,141 +743,2532 @@ impl NodeVisitor for Interpreter {
         Value::None
     }
 
-    fn visit_procedure_decl(&mut self, _: &mut Node) -> Value {
+    fn visit_procedure_decl(&mut self, procedure_decl: &mut Node) -> Value {
+        // A FORWARD declaration is only there for the analyzer, the
+        // definition that follows brings the block
+        if let Node::ProcedureDecl(decl @ ProcedureDecl { block: Some(_), .. }) = procedure_decl {
+            self.call_stack
+                .peek()
+                .unwrap()
+                .set_routine(decl.name.to_lowercase(), decl.clone());
+        }
+
         Value::None
     }
 
     fn visit_procedure_call(&mut self, procedure_call: &mut ProcedureCall) -> Value {
-        let mut ar = ActivationRecord::new(procedure_call.proc_name.clone(), ARType::Procedure, 2);
+        let (env, mut routine) = match self.resolve_routine(&procedure_call.proc_name) {
+            Some(routine) => routine,
+            None => return self.call_builtin(procedure_call),
+        };
+        let ar_type = match routine.return_type {
+            Some(_) => ARType::Function,
+            None => ARType::Procedure,
+        };
+        let nesting_level = self.call_stack.get(env).nesting_level + 1;
+        let mut ar = ActivationRecord::new(routine.name.clone(), ar_type, nesting_level);
+        ar.access_link = Some(env);
 
-        let formal_params = &procedure_call
-            .proc_symbol
-            .as_mut()
-            .unwrap()
-            .formal_params
-            .clone();
         let actual_params = &mut procedure_call.actual_params;
-        for (param_symbol, argument_node) in formal_params.iter().zip(actual_params.iter_mut()) {
-            ar.set(param_symbol.name.clone(), self.visit(argument_node));
+        for (param, argument_node) in routine.params.iter().zip(actual_params.iter_mut()) {
+            let name = param.var_node.value.expect_string().to_lowercase();
+            let type_ = &param.var_node.type_symbol;
+            let conformant = matches!(param.type_node, Type::Conformant(..));
+            match argument_node {
+                Node::Var(var) if param.by_reference => {
+                    let reference = self.reference(var);
+                    if conformant {
+                        bind_bounds(&mut ar, &param.type_node, type_, &self.load(&reference));
+                    }
+                    ar.set_reference(name, reference)
+                }
+                _ => {
+                    let value = coerce(self.visit(argument_node), type_);
+                    self.check_range(&value, type_);
+                    if conformant {
+                        bind_bounds(&mut ar, &param.type_node, type_, &value);
+                    }
+                    ar.set(name, value)
+                }
+            }
+        }
+        if routine.return_type.is_some() {
+            ar.set(String::from(ActivationRecord::RESULT), Value::None);
+            if routine.result_alias {
+                let result = String::from(ActivationRecord::RESULT);
+                let record = self.call_stack.len();
+                ar.set_reference(String::from("result"), Reference::new(record, result));
+            }
         }
 
         self.call_stack.push(ar);
-        println!("ENTER PROCEDURE: {}", &procedure_call.proc_name);
+        self.visit_block(routine.block.as_mut().unwrap());
+        let ar = self.call_stack.pop().unwrap();
 
-        self.visit_block(
-            &mut (procedure_call
-                .proc_symbol
-                .as_ref()
-                .unwrap()
-                .block_ast
-                .as_ref()
-                .unwrap()
-                .clone()),
-        );
+        match routine.return_type {
+            Some(_) => match ar.get(String::from(ActivationRecord::RESULT)) {
+                Some(Value::None) | None => {
+                    RuntimeError::new(format!(
+                        "Function {} returned without a result",
+                        procedure_call.proc_name
+                    ))
+                    .throw();
+                    unreachable!()
+                }
+                Some(result) => result.clone(),
+            },
+            None => Value::None,
+        }
+    }
 
-        println!("{:#?}", self.call_stack);
-        println!("EXIT PROCEDURE: {}", &procedure_call.proc_name);
-        self.call_stack.pop();
+    fn visit_if(&mut self, if_: &mut Node) -> Value {
+        if let Node::If(_, condition, consequence, alternative) = if_ {
+            if self.visit(condition).expect_boolean() {
+                self.visit(consequence);
+            } else if let Some(alternative) = alternative {
+                self.visit(alternative);
+            }
+        }
 
         Value::None
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    use crate::{
-        lexer::Lexer,
-        parser::Parser,
-        tokens::{Token, TokenType, Value},
-    };
+    fn visit_while(&mut self, while_: &mut Node) -> Value {
+        if let Node::While(_, condition, body) = while_ {
+            while self.visit(condition).expect_boolean() {
+                self.visit(body);
+            }
+        }
 
-    #[test]
-    fn binary_ops() {
-        let mul = Token::new(TokenType::Mul, Value::Char('*'), 1, 1);
-        let plus = Token::new(TokenType::Plus, Value::Char('+'), 1, 1);
-        let two = Value::Float(2.0);
-        let seven = Value::Float(7.0);
-        let three = Value::Float(3.0);
+        Value::None
+    }
 
-        let mut add_node = Node::BinOp(
-            // left
-            Box::new(Node::BinOp(
-                // left
-                Box::new(Node::Num(two)),
-                // op
-                mul,
-                // right
-                Box::new(Node::Num(seven)),
-            )),
-            // op
-            plus,
-            // right
-            Box::new(Node::Num(three)),
-        );
+    fn visit_repeat(&mut self, repeat: &mut Node) -> Value {
+        if let Node::Repeat(_, body, condition) = repeat {
+            loop {
+                for statement in body.iter_mut() {
+                    self.visit(statement);
+                }
+                if self.visit(condition).expect_boolean() {
+                    break;
+                }
+            }
+        }
 
-        let mut inperpreter = Interpreter::new();
-        let res = inperpreter.visit(&mut add_node);
-        assert_eq!(res, Value::Float(17.0));
+        Value::None
     }
 
-    #[test]
-    fn unary_op() {
-        let five = Value::Float(5.0);
-        let two = Value::Float(2.0);
-        let minus = Token::new(TokenType::Minus, Value::Char('-'), 1, 1);
+    fn visit_for(&mut self, for_: &mut Node) -> Value {
+        if let Node::For(control, start, direction, end, body) = for_ {
+            // Both bounds are evaluated exactly once, before the first iteration
+            let start = self.visit(start);
+            let end = self.visit(end);
+            let name = control.value.expect_string().to_lowercase();
+            let (first, last) = (start.expect_ordinal(), end.expect_ordinal());
+            let ordinals: Box<dyn Iterator<Item = i32>> = match direction.type_ {
+                TokenType::Downto => Box::new((last..=first).rev()),
+                _ => Box::new(first..=last),
+            };
 
-        // 5 - -2
-        let mut expr_node = Node::BinOp(
-            // left
-            Box::new(Node::Num(five)),
-            // op
-            minus.clone(),
-            // right
-            Box::new(Node::UnaryOp(
-                // op
-                minus.clone(),
-                // right
-                Box::new(Node::UnaryOp(minus, Box::new(Node::Num(two)))),
-            )),
-        );
+            for ordinal in ordinals {
+                self.call_stack
+                    .peek()
+                    .unwrap()
+                    .set(name.clone(), start.with_ordinal(ordinal));
+                self.visit(body);
+            }
 
-        let mut inperpreter = Interpreter::new();
-        let res = inperpreter.visit(&mut expr_node);
-        assert_eq!(res, Value::Float(3.0));
+            // The control variable is undefined once the loop has finished
+            self.call_stack.peek().unwrap().set(name, Value::None);
+        }
+
+        Value::None
     }
 
-    #[test]
-    fn variables() {
-        let text = "
-    PROGRAM VarTest;
+    fn visit_case(&mut self, case: &mut Node) -> Value {
+        if let Node::Case(_, selector, arms, otherwise) = case {
+            let value = self.visit(selector);
+            let ordinal = value.expect_ordinal();
 
-    BEGIN
-        BEGIN
-            number := 2;
-            a := nUmbEr;
-            b := 10 * a + 10 * NUMBER DIV 4;
-            c := a - - b;
-        END;
+            for arm in arms {
+                for (low, high) in &mut arm.labels {
+                    let low = self.visit(low).expect_ordinal();
+                    let high = match high {
+                        Some(high) => self.visit(high).expect_ordinal(),
+                        None => low,
+                    };
+                    if low <= ordinal && ordinal <= high {
+                        self.visit(&mut arm.statement);
+                        return Value::None;
+                    }
+                }
+            }
 
-        _x := 11;
-    END.";
+            match otherwise {
+                Some(statements) => {
+                    for statement in statements {
+                        self.visit(statement);
+                    }
+                }
+                None => RuntimeError::new(format!("No CASE label matches value {}", value)).throw(),
+            }
+        }
 
-        let lexer = Lexer::new(text.to_string());
-        let mut parser = Parser::new(lexer);
-        let mut tree = parser.parse();
-        let mut interpreter = Interpreter::new();
-        interpreter.visit(&mut tree);
+        Value::None
+    }
 
-        let mut expected = CallStack::new();
-        let mut ar = ActivationRecord::new(String::from("VarTest"), ARType::Program, 1);
-        ar.set(String::from("number"), Value::Integer(2));
-        ar.set(String::from("a"), Value::Integer(2));
-        ar.set(String::from("_x"), Value::Integer(11));
-        ar.set(String::from("c"), Value::Integer(27));
-        ar.set(String::from("b"), Value::Integer(25));
-        expected.push(ar);
-        assert_eq!(interpreter.call_stack, expected);
+    fn visit_set(&mut self, set: &mut Node) -> Value {
+        if let Node::Set(_, elements) = set {
+            let mut set = BTreeSet::new();
+            for (low, high) in elements {
+                let low = self.visit(low).expect_ordinal();
+                let high = match high {
+                    Some(high) => self.visit(high).expect_ordinal(),
+                    None => low,
+                };
+                if let Some(ordinal) = [low, high].iter().find(|o| !(0..=255).contains(*o)) {
+                    RuntimeError::new(format!("Set element {} out of range 0..255", ordinal))
+                        .throw();
+                }
+                // A range from a higher to a lower value is empty
+                set.extend(low..=high);
+            }
+            Value::Set(set)
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Gives the text of a formatted argument, as Write and Str use it
+    fn visit_formatted(&mut self, formatted: &mut Node) -> Value {
+        if let Node::Formatted(_, expr, width, precision) = formatted {
+            let value = self.visit(expr);
+            let width = self.visit(width).expect_ordinal();
+            let precision = precision
+                .as_mut()
+                .map(|precision| self.visit(precision).expect_ordinal());
+            Value::String(builtins::format(&value, Some(width), precision))
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+/// The value a variable of the given type starts out with. Arrays are
+/// allocated with all their elements, which are undefined like scalars.
+fn default_value(type_: &Symbol) -> Value {
+    match type_.definition() {
+        Symbol::Array(index, element) => {
+            let (low, high) = index.bounds().unwrap();
+            Value::Array(low, vec![default_value(element); (high - low + 1) as usize])
+        }
+        Symbol::Record(fields) => Value::Record(
+            fields
+                .iter()
+                .map(|field| (field.name.clone(), default_value(&field.type_)))
+                .collect(),
+        ),
+        // Dynamic arrays start out empty
+        Symbol::DynArray(_) => Value::DynArray(Rc::new(RefCell::new(Vec::new()))),
+        _ => Value::None,
+    }
+}
+
+/// The value of a variable of the given type whose memory is cleared, as
+/// Free Pascal does for the elements SetLength adds
+fn zero_value(type_: &Symbol) -> Value {
+    match type_.definition() {
+        Symbol::Array(index, element) => {
+            let (low, high) = index.bounds().unwrap();
+            Value::Array(low, vec![zero_value(element); (high - low + 1) as usize])
+        }
+        Symbol::Record(fields) => Value::Record(
+            fields
+                .iter()
+                .map(|field| (field.name.clone(), zero_value(&field.type_)))
+                .collect(),
+        ),
+        Symbol::Set(_) => Value::Set(BTreeSet::new()),
+        Symbol::Pointer(_) => Value::Pointer(None),
+        _ if type_.is_string() => Value::String(String::new()),
+        _ if type_.is_builtin("REAL") => Value::Float(0.0),
+        _ if type_.is_ordinal() => builtins::ordinal_value(type_, 0),
+        _ => default_value(type_),
+    }
+}
+
+/// Converts an INTEGER value stored into a REAL variable or a CHAR stored
+/// into a STRING, cuts strings down to the length of a STRING[n], and copies
+/// a dynamic array passed to an open array value parameter
+pub fn coerce(value: Value, type_: &Option<Symbol>) -> Value {
+    match (value, type_.as_ref().map(Symbol::definition)) {
+        (Value::DynArray(elements), Some(Symbol::OpenArray(_))) => {
+            Value::Array(0, elements.borrow().clone())
+        }
+        (Value::Integer(i), Some(type_)) if type_.is_builtin("REAL") => Value::Float(i as f32),
+        (Value::Char(c), Some(type_)) if type_.is_string() => {
+            coerce(Value::String(c.to_string()), &Some(type_.clone()))
+        }
+        (Value::String(s), Some(Symbol::String(length))) => {
+            Value::String(s.chars().take(*length as usize).collect())
+        }
+        (value, _) => value,
+    }
+}
+
+/// Applies an operator to two integers. Apart from the shifts, which drop
+/// the bits moved out, the result has to be exact.
+fn integer_operation(op: &TokenType, left: i32, right: i32) -> i32 {
+    if let (TokenType::IntegerDiv | TokenType::Mod, 0) = (op, right) {
+        RuntimeError::new(String::from("Division by zero")).throw();
+    }
+    match op {
+        TokenType::Plus => builtins::checked(left.checked_add(right)),
+        TokenType::Minus => builtins::checked(left.checked_sub(right)),
+        TokenType::Mul => builtins::checked(left.checked_mul(right)),
+        // DIV truncates towards zero
+        TokenType::IntegerDiv => builtins::checked(left.checked_div(right)),
+        // ISO 7185 defines MOD only for a positive divisor, and its result
+        // is never negative
+        TokenType::Mod if right < 0 => {
+            RuntimeError::new(format!("MOD by negative number {}", right)).throw();
+            unreachable!()
+        }
+        TokenType::Mod => left.rem_euclid(right),
+        TokenType::And => left & right,
+        TokenType::Or => left | right,
+        TokenType::Xor => left ^ right,
+        // Shifts are logical and only use the lowest 5 bits of the count,
+        // like Free Pascal's on 32-bit integers
+        TokenType::Shl => (left as u32).wrapping_shl(right as u32) as i32,
+        TokenType::Shr => (left as u32).wrapping_shr(right as u32) as i32,
+        _ => unreachable!(),
+    }
+}
+
+fn offset(low: i32, len: usize, index: i32) -> usize {
+    let offset = index as i64 - low as i64;
+    if offset < 0 || offset >= len as i64 {
+        RuntimeError::new(format!(
+            "Index {} out of bounds {}..{}",
+            index,
+            low,
+            low as i64 + len as i64 - 1
+        ))
+        .throw();
+    }
+    offset as usize
+}
+
+/// Finds a field of a record, checking that its variant is the active one
+fn field_position(fields: &[(String, Value)], name: &str, guards: &[Guard]) -> usize {
+    let position = |name: &str| {
+        fields
+            .iter()
+            .position(|(field, _)| field.eq_ignore_ascii_case(name))
+            .unwrap()
+    };
+    for guard in guards {
+        let active = match &fields[position(&guard.tag)].1 {
+            Value::None => false,
+            tag => {
+                let tag = tag.expect_ordinal();
+                guard
+                    .labels
+                    .iter()
+                    .any(|&(low, high)| low <= tag && tag <= high)
+            }
+        };
+        if !active {
+            RuntimeError::new(format!("Field {} is not in the active variant", name)).throw();
+        }
+    }
+    position(name)
+}
+
+/// Gives the bound identifiers of a conformant array parameter the bounds of
+/// the array passed to it
+fn bind_bounds(ar: &mut ActivationRecord, type_node: &Type, type_: &Option<Symbol>, value: &Value) {
+    if let (
+        Type::Conformant(_, low, high, _, element_node),
+        Some(Symbol::Conformant(index, element)),
+        Value::Array(first, elements),
+    ) = (type_node, type_.as_ref().map(Symbol::definition), value)
+    {
+        let last = first + elements.len() as i32 - 1;
+        let bounds = [(low, *first), (high, last)];
+        for (bound, ordinal) in bounds {
+            let name = bound.value.expect_string().to_lowercase();
+            ar.set(name, builtins::ordinal_value(index, ordinal));
+        }
+        // Inner arrays all have the bounds of the first one
+        if let Some(inner) = elements.first() {
+            bind_bounds(ar, element_node, &Some(*element.clone()), inner);
+        }
+    }
+}
+
+/// Reads the part of a value a path leads to
+fn load_component(value: &Value, path: &[Component]) -> Value {
+    let (component, rest) = match path.split_first() {
+        Some(step) => step,
+        None => return value.clone(),
+    };
+    match (value, component) {
+        // Characters of a string aren't values of their own
+        (Value::String(s), Component::Index(index)) => {
+            let chars: Vec<char> = s.chars().collect();
+            Value::Char(chars[offset(1, chars.len(), *index)])
+        }
+        // The elements of a dynamic array can only be reached while it is
+        // borrowed
+        (Value::DynArray(elements), Component::Index(index) | Component::Position(index)) => {
+            let elements = elements.borrow();
+            load_component(&elements[offset(0, elements.len(), *index)], rest)
+        }
+        (Value::None, _) => Value::None,
+        _ => load_component(element(value, component), rest),
+    }
+}
+
+/// Replaces the part of a value a path leads to, which fails when the path
+/// goes through something undefined
+fn store_component(slot: &mut Value, path: &[Component], value: Value) -> bool {
+    let (component, rest) = match path.split_first() {
+        Some(step) => step,
+        None => {
+            *slot = value;
+            return true;
+        }
+    };
+    match (slot, component) {
+        (Value::String(s), Component::Index(index)) => {
+            let mut chars: Vec<char> = s.chars().collect();
+            let offset = offset(1, chars.len(), *index);
+            chars[offset] = match value {
+                Value::Char(c) => c,
+                _ => unreachable!(),
+            };
+            *s = chars.into_iter().collect();
+            true
+        }
+        (Value::DynArray(elements), Component::Index(index) | Component::Position(index)) => {
+            let mut elements = elements.borrow_mut();
+            let offset = offset(0, elements.len(), *index);
+            store_component(&mut elements[offset], rest, value)
+        }
+        (Value::None, _) => false,
+        (slot, component) => store_component(element_mut(slot, component), rest, value),
+    }
+}
+
+fn element<'a>(value: &'a Value, component: &Component) -> &'a Value {
+    match (value, component) {
+        (Value::Array(low, elements), Component::Index(index)) => {
+            &elements[offset(*low, elements.len(), *index)]
+        }
+        (Value::Array(_, elements), Component::Position(index)) => {
+            &elements[offset(0, elements.len(), *index)]
+        }
+        (Value::Record(fields), Component::Field(name, guards)) => {
+            &fields[field_position(fields, name, guards)].1
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn element_mut<'a>(value: &'a mut Value, component: &Component) -> &'a mut Value {
+    match (value, component) {
+        (Value::Array(low, elements), Component::Index(index)) => {
+            let offset = offset(*low, elements.len(), *index);
+            &mut elements[offset]
+        }
+        (Value::Array(_, elements), Component::Position(index)) => {
+            let offset = offset(0, elements.len(), *index);
+            &mut elements[offset]
+        }
+        (Value::Record(fields), Component::Field(name, guards)) => {
+            let position = field_position(fields, name, guards);
+            &mut fields[position].1
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => Some(l.cmp(r)),
+        (Value::Boolean(l), Value::Boolean(r)) => Some(l.cmp(r)),
+        (Value::Char(l), Value::Char(r)) => Some(l.cmp(r)),
+        (Value::Enum(l, _), Value::Enum(r, _)) => Some(l.cmp(r)),
+        // Pointers are only ever equal or not
+        (Value::Pointer(l), Value::Pointer(r)) => (l == r).then_some(Ordering::Equal),
+        (Value::String(_) | Value::Char(_), Value::String(_) | Value::Char(_)) => {
+            Some(left.expect_string().cmp(&right.expect_string()))
+        }
+        (l, r) => l.expect_number().partial_cmp(&r.expect_number()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        builtins::Dialect,
+        lexer::Lexer,
+        parser::Parser,
+        semantic_analyzer::SemanticAnalyzer,
+        tokens::{Token, TokenType, Value},
+    };
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    /// Collects what a program writes
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn interpret(text: &str) -> Interpreter {
+        run(text, "").0
+    }
+
+    /// Runs a program with the given input, returning what it wrote
+    fn run(text: &str, input: &str) -> (Interpreter, String) {
+        // The tests use every extension there is
+        run_as(Dialect::Turbo, text, input)
+    }
+
+    /// Runs a program in the given dialect with range and heap checks on,
+    /// so that the tests see every error they can report
+    fn run_as(dialect: Dialect, text: &str, input: &str) -> (Interpreter, String) {
+        let lexer = Lexer::new(text.to_string());
+        let mut parser = Parser::new(lexer);
+        let mut tree = parser.parse();
+        SemanticAnalyzer::new(dialect).visit(&mut tree);
+        let output = Output::default();
+        let mut interpreter = Interpreter::with_io(
+            Box::new(Cursor::new(input.to_string())),
+            Box::new(output.clone()),
+        );
+        interpreter.enable_range_checks();
+        interpreter.enable_heap_checks();
+        interpreter.visit(&mut tree);
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        (interpreter, output)
+    }
+
+    fn global(interpreter: &mut Interpreter, name: &str) -> Value {
+        interpreter
+            .call_stack
+            .peek()
+            .unwrap()
+            .get(name.to_string())
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn binary_ops() {
+        let mul = Token::new(TokenType::Mul, Value::Char('*'), 1, 1);
+        let plus = Token::new(TokenType::Plus, Value::Char('+'), 1, 1);
+        let two = Value::Float(2.0);
+        let seven = Value::Float(7.0);
+        let three = Value::Float(3.0);
+
+        let mut add_node = Node::BinOp(
+            // left
+            Box::new(Node::BinOp(
+                // left
+                Box::new(Node::Num(two)),
+                // op
+                mul,
+                // right
+                Box::new(Node::Num(seven)),
+            )),
+            // op
+            plus,
+            // right
+            Box::new(Node::Num(three)),
+        );
+
+        let mut inperpreter = Interpreter::new();
+        let res = inperpreter.visit(&mut add_node);
+        assert_eq!(res, Value::Float(17.0));
+    }
+
+    #[test]
+    fn unary_op() {
+        let five = Value::Float(5.0);
+        let two = Value::Float(2.0);
+        let minus = Token::new(TokenType::Minus, Value::Char('-'), 1, 1);
+
+        // 5 - -2
+        let mut expr_node = Node::BinOp(
+            // left
+            Box::new(Node::Num(five)),
+            // op
+            minus.clone(),
+            // right
+            Box::new(Node::UnaryOp(
+                // op
+                minus.clone(),
+                // right
+                Box::new(Node::UnaryOp(minus, Box::new(Node::Num(two)))),
+            )),
+        );
+
+        let mut inperpreter = Interpreter::new();
+        let res = inperpreter.visit(&mut expr_node);
+        assert_eq!(res, Value::Float(3.0));
+    }
+
+    #[test]
+    fn variables() {
+        let text = "
+    PROGRAM VarTest;
+
+    BEGIN
+        BEGIN
+            number := 2;
+            a := nUmbEr;
+            b := 10 * a + 10 * NUMBER DIV 4;
+            c := a - - b;
+        END;
+
+        _x := 11;
+    END.";
+
+        let lexer = Lexer::new(text.to_string());
+        let mut parser = Parser::new(lexer);
+        let mut tree = parser.parse();
+        let mut interpreter = Interpreter::new();
+        interpreter.visit(&mut tree);
+
+        let mut expected = CallStack::new();
+        let mut ar = ActivationRecord::new(String::from("VarTest"), ARType::Program, 1);
+        ar.set(String::from("number"), Value::Integer(2));
+        ar.set(String::from("a"), Value::Integer(2));
+        ar.set(String::from("_x"), Value::Integer(11));
+        ar.set(String::from("c"), Value::Integer(27));
+        ar.set(String::from("b"), Value::Integer(25));
+        expected.push(ar);
+        assert_eq!(interpreter.call_stack, expected);
+    }
+
+    #[test]
+    fn if_statements() {
+        let text = "
+PROGRAM Branches;
+VAR
+    a, b, c : INTEGER;
+    big, flag : BOOLEAN;
+
+BEGIN
+    a := 3;
+    big := a * 2 >= 6;
+    flag := NOT big OR (a <> 3);
+    IF big AND NOT flag THEN b := 1 ELSE b := 2;
+    IF a < 2 THEN
+        c := 1
+    ELSE IF a = 3 THEN
+        c := 2
+    ELSE
+        c := 3
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "big"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "flag"), Value::Boolean(false));
+        assert_eq!(global(&mut interpreter, "b"), Value::Integer(1));
+        assert_eq!(global(&mut interpreter, "c"), Value::Integer(2));
+    }
+
+    #[test]
+    fn dangling_else() {
+        let text = "
+PROGRAM DanglingElse;
+VAR
+    x : INTEGER;
+
+BEGIN
+    x := 0;
+    IF FALSE THEN IF TRUE THEN x := 1 ELSE x := 2
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "x"), Value::Integer(0));
+    }
+
+    #[test]
+    fn nested_loops() {
+        let text = "
+PROGRAM Loops;
+VAR
+    i, j, total, steps : INTEGER;
+
+BEGIN
+    total := 0;
+    steps := 0;
+    i := 1;
+    WHILE i <= 4 DO
+    BEGIN
+        j := 0;
+        REPEAT
+            j := j + 1;
+            total := total + i * j
+        UNTIL j >= i;
+        i := i + 1
+    END;
+    REPEAT
+        steps := steps + 1
+    UNTIL TRUE;
+    WHILE FALSE DO steps := 100
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "i"), Value::Integer(5));
+        assert_eq!(global(&mut interpreter, "total"), Value::Integer(65));
+        assert_eq!(global(&mut interpreter, "steps"), Value::Integer(1));
+    }
+
+    #[test]
+    fn loop_calling_procedure() {
+        let text = "
+PROGRAM LoopCalls;
+VAR
+    i : INTEGER;
+
+PROCEDURE Countdown(n : INTEGER);
+BEGIN
+    WHILE n > 0 DO
+        n := n - 1
+END;
+
+BEGIN
+    i := 0;
+    REPEAT
+        Countdown(i);
+        i := i + 1
+    UNTIL i = 3
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "i"), Value::Integer(3));
+    }
+
+    #[test]
+    fn for_loops() {
+        let text = "
+PROGRAM Counting;
+VAR
+    i, j, n, sum, down, outer : INTEGER;
+    b : BOOLEAN;
+
+BEGIN
+    sum := 0;
+    n := 3;
+    FOR i := 1 TO n DO
+    BEGIN
+        n := n + 1;
+        FOR j := i DOWNTO 1 DO
+            sum := sum + j
+    END;
+    down := 0;
+    FOR i := 5 DOWNTO 6 DO
+        down := down + 1;
+    outer := 0;
+    FOR b := FALSE TO TRUE DO
+        outer := outer + 1
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "sum"), Value::Integer(10));
+        assert_eq!(global(&mut interpreter, "n"), Value::Integer(6));
+        assert_eq!(global(&mut interpreter, "down"), Value::Integer(0));
+        assert_eq!(global(&mut interpreter, "outer"), Value::Integer(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable i is undefined")]
+    fn control_variable_undefined_after_loop() {
+        let text = "
+PROGRAM Undefined;
+VAR
+    i, x : INTEGER;
+
+BEGIN
+    FOR i := 1 TO 3 DO
+        x := i;
+    x := i
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn case_statements() {
+        let text = "
+PROGRAM Dispatch;
+VAR
+    i, small, ranged, other : INTEGER;
+    b : BOOLEAN;
+
+BEGIN
+    small := 0;
+    ranged := 0;
+    other := 0;
+    FOR i := -2 TO 12 DO
+        CASE i OF
+            1, 2, 3: small := small + 1;
+            -2..0, 10..11: ranged := ranged + 1;
+        OTHERWISE
+            other := other + 1;
+            other := other + 1
+        END;
+    b := TRUE;
+    CASE b OF
+        FALSE: i := 0;
+        TRUE: i := 1
+    END
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "small"), Value::Integer(3));
+        assert_eq!(global(&mut interpreter, "ranged"), Value::Integer(5));
+        assert_eq!(global(&mut interpreter, "other"), Value::Integer(14));
+        assert_eq!(global(&mut interpreter, "i"), Value::Integer(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "No CASE label matches value 4")]
+    fn case_without_match() {
+        let text = "
+PROGRAM NoMatch;
+VAR
+    x : INTEGER;
+
+BEGIN
+    x := 4;
+    CASE x OF
+        1..3: x := 0;
+        5: x := 1
+    END
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn functions() {
+        let text = "
+PROGRAM Functions;
+VAR
+    x, y : INTEGER;
+    r : REAL;
+    even : BOOLEAN;
+
+FUNCTION Square(n : INTEGER) : INTEGER;
+BEGIN
+    Square := n * n
+END;
+
+FUNCTION Average(a, b : INTEGER) : REAL;
+BEGIN
+    Result := (a + b) / 2
+END;
+
+FUNCTION IsEven(n : INTEGER) : BOOLEAN;
+BEGIN
+    IsEven := n DIV 2 * 2 = n
+END;
+
+FUNCTION Seven : INTEGER;
+BEGIN
+    Seven := 7
+END;
+
+BEGIN
+    x := Square(3) + Square(Square(2));
+    y := Seven * 2;
+    r := Average(x, Seven);
+    even := IsEven(y) AND NOT IsEven(Seven)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "x"), Value::Integer(25));
+        assert_eq!(global(&mut interpreter, "y"), Value::Integer(14));
+        assert_eq!(global(&mut interpreter, "r"), Value::Float(16.0));
+        assert_eq!(global(&mut interpreter, "even"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn result_set_by_nested_procedure() {
+        let text = "
+PROGRAM Nested;
+VAR
+    x : INTEGER;
+
+FUNCTION F(n : INTEGER) : INTEGER;
+
+    PROCEDURE SetIt;
+    BEGIN
+        F := n * 2
+    END;
+
+BEGIN
+    SetIt
+END;
+
+BEGIN
+    x := F(21)
+END.";
+
+        let mut interpreter = run_as(Dialect::Iso, text, "").0;
+        assert_eq!(global(&mut interpreter, "x"), Value::Integer(42));
+    }
+
+    #[test]
+    #[should_panic(expected = "Function Nothing returned without a result")]
+    fn function_without_result() {
+        let text = "
+PROGRAM NoResult;
+VAR
+    x : INTEGER;
+
+FUNCTION Nothing(n : INTEGER) : INTEGER;
+BEGIN
+END;
+
+BEGIN
+    x := Nothing(1)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn var_parameters() {
+        let text = "
+PROGRAM References;
+VAR
+    a, b, count : INTEGER;
+    total : REAL;
+
+PROCEDURE Swap(VAR x, y : INTEGER);
+VAR
+    tmp : INTEGER;
+BEGIN
+    tmp := x;
+    x := y;
+    y := tmp
+END;
+
+FUNCTION Accumulate(VAR sum : REAL; VAR n : INTEGER; value : REAL) : REAL;
+BEGIN
+    sum := sum + value;
+    n := n + 1;
+    value := 0;
+    Accumulate := sum / n
+END;
+
+BEGIN
+    a := 1;
+    b := 2;
+    Swap(a, b);
+    total := 0.5;
+    count := 0;
+    total := Accumulate(total, count, 1.5) + Accumulate(total, count, 4)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "a"), Value::Integer(2));
+        assert_eq!(global(&mut interpreter, "b"), Value::Integer(1));
+        assert_eq!(global(&mut interpreter, "count"), Value::Integer(2));
+        assert_eq!(global(&mut interpreter, "total"), Value::Float(5.0));
+    }
+
+    #[test]
+    fn nested_scopes() {
+        let text = "
+PROGRAM Scopes;
+VAR
+    x, log, calls : INTEGER;
+
+PROCEDURE Level1(n : INTEGER);
+VAR
+    x : INTEGER;
+
+    PROCEDURE Level2;
+    VAR
+        y : INTEGER;
+
+        PROCEDURE Level3;
+        BEGIN
+            calls := calls + 1;
+            log := log * 10 + x + y
+        END;
+
+    BEGIN
+        y := n;
+        Level3
+    END;
+
+    PROCEDURE Sibling;
+    BEGIN
+        Level2
+    END;
+
+BEGIN
+    x := 0;
+    Sibling;
+    IF n > 1 THEN Level1(n - 1);
+    Level2
+END;
+
+BEGIN
+    x := 7;
+    log := 0;
+    calls := 0;
+    Level1(3)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "x"), Value::Integer(7));
+        assert_eq!(global(&mut interpreter, "calls"), Value::Integer(6));
+        assert_eq!(global(&mut interpreter, "log"), Value::Integer(321123));
+    }
+
+    #[test]
+    fn recursion() {
+        let text = "
+PROGRAM Recursion;
+VAR
+    fact, fib, depth : INTEGER;
+
+FUNCTION Factorial(n : INTEGER) : INTEGER;
+BEGIN
+    IF n <= 1 THEN
+        Factorial := 1
+    ELSE
+        Factorial := n * Factorial(n - 1)
+END;
+
+FUNCTION Fibonacci(n : INTEGER) : INTEGER;
+    FUNCTION Helper(a, b, k : INTEGER) : INTEGER;
+    BEGIN
+        IF k = 0 THEN Helper := a ELSE Helper := Helper(b, a + b, k - 1)
+    END;
+BEGIN
+    Fibonacci := Helper(0, 1, n)
+END;
+
+PROCEDURE Countdown(n : INTEGER; VAR deepest : INTEGER);
+VAR
+    local : INTEGER;
+
+    PROCEDURE Track;
+    BEGIN
+        IF local > deepest THEN deepest := local
+    END;
+
+BEGIN
+    local := 10 - n;
+    IF n > 0 THEN Countdown(n - 1, deepest);
+    Track
+END;
+
+BEGIN
+    fact := Factorial(6);
+    fib := Fibonacci(10);
+    depth := 0;
+    Countdown(4, depth)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "fact"), Value::Integer(720));
+        assert_eq!(global(&mut interpreter, "fib"), Value::Integer(55));
+        assert_eq!(global(&mut interpreter, "depth"), Value::Integer(10));
+    }
+
+    #[test]
+    fn arrays() {
+        let text = "
+PROGRAM Arrays;
+VAR
+    i, j, sum : INTEGER;
+    squares, copy : ARRAY[-2..2] OF INTEGER;
+    grid : ARRAY[1..3, 0..1] OF INTEGER;
+    rows : ARRAY[1..2] OF ARRAY[1..2] OF REAL;
+    flags : ARRAY[BOOLEAN] OF INTEGER;
+
+PROCEDURE Double(VAR n : INTEGER);
+BEGIN
+    n := n * 2
+END;
+
+BEGIN
+    FOR i := -2 TO 2 DO
+        squares[i] := i * i;
+    copy := squares;
+    squares[0] := 100;
+    Double(copy[2]);
+
+    FOR i := 1 TO 3 DO
+        FOR j := 0 TO 1 DO
+            grid[i, j] := 10 * i + j;
+    sum := grid[3][1] + grid[1, 0];
+
+    rows[2, 1] := 3;
+    flags[TRUE] := 1;
+    flags[1 > 2] := squares[copy[-1] - 1]
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(
+            global(&mut interpreter, "squares"),
+            Value::Array(
+                -2,
+                vec![
+                    Value::Integer(4),
+                    Value::Integer(1),
+                    Value::Integer(100),
+                    Value::Integer(1),
+                    Value::Integer(4)
+                ]
+            )
+        );
+        assert_eq!(
+            global(&mut interpreter, "copy"),
+            Value::Array(
+                -2,
+                vec![
+                    Value::Integer(4),
+                    Value::Integer(1),
+                    Value::Integer(0),
+                    Value::Integer(1),
+                    Value::Integer(8)
+                ]
+            )
+        );
+        assert_eq!(global(&mut interpreter, "sum"), Value::Integer(41));
+        assert_eq!(
+            global(&mut interpreter, "rows"),
+            Value::Array(
+                1,
+                vec![
+                    Value::Array(1, vec![Value::None, Value::None]),
+                    Value::Array(1, vec![Value::Float(3.0), Value::None])
+                ]
+            )
+        );
+        assert_eq!(
+            global(&mut interpreter, "flags"),
+            Value::Array(0, vec![Value::Integer(100), Value::Integer(1)])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 4 out of bounds 1..3")]
+    fn array_index_out_of_bounds() {
+        let text = "
+PROGRAM Bounds;
+VAR
+    i : INTEGER;
+    a : ARRAY[1..3] OF INTEGER;
+
+BEGIN
+    i := 4;
+    a[i] := 0
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn records() {
+        let text = "
+PROGRAM Records;
+VAR
+    p, q : RECORD
+        x, y : INTEGER;
+        tag : RECORD visible : BOOLEAN END
+    END;
+    points : ARRAY[1..2] OF RECORD x, y : REAL END;
+    sum : REAL;
+
+PROCEDURE Move(VAR r : REAL; by : REAL);
+BEGIN
+    r := r + by
+END;
+
+BEGIN
+    p.x := 1;
+    p.y := 2;
+    p.tag.visible := TRUE;
+    q := p;
+    q.x := 10;
+    q.tag.visible := NOT p.tag.visible;
+
+    points[1].x := 0.5;
+    points[1].y := p.y;
+    points[2] := points[1];
+    Move(points[2].x, 2);
+    sum := points[1].x + points[2].x
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(
+            global(&mut interpreter, "p"),
+            Value::Record(vec![
+                (String::from("x"), Value::Integer(1)),
+                (String::from("y"), Value::Integer(2)),
+                (
+                    String::from("tag"),
+                    Value::Record(vec![(String::from("visible"), Value::Boolean(true))])
+                )
+            ])
+        );
+        assert_eq!(
+            global(&mut interpreter, "q"),
+            Value::Record(vec![
+                (String::from("x"), Value::Integer(10)),
+                (String::from("y"), Value::Integer(2)),
+                (
+                    String::from("tag"),
+                    Value::Record(vec![(String::from("visible"), Value::Boolean(false))])
+                )
+            ])
+        );
+        assert_eq!(global(&mut interpreter, "sum"), Value::Float(3.0));
+    }
+
+    #[test]
+    fn variant_records() {
+        let text = "
+PROGRAM Variants;
+VAR
+    shape : RECORD
+        id : INTEGER;
+        CASE kind : INTEGER OF
+            1: (radius : REAL);
+            2, 3: (width, height : REAL;
+                   CASE filled : BOOLEAN OF
+                       TRUE: (color : INTEGER))
+    END;
+    area : REAL;
+
+BEGIN
+    shape.kind := 1;
+    shape.radius := 2;
+    area := 3 * shape.radius * shape.radius;
+    shape.kind := 3;
+    shape.width := 2;
+    shape.height := area;
+    shape.filled := TRUE;
+    shape.color := 7;
+    area := shape.width * shape.height
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "area"), Value::Float(24.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Field radius is not in the active variant")]
+    fn inactive_variant() {
+        let text = "
+PROGRAM Inactive;
+VAR
+    area : REAL;
+    shape : RECORD
+        CASE kind : INTEGER OF
+            1: (radius : REAL);
+            2: (width, height : REAL)
+    END;
+
+BEGIN
+    shape.kind := 2;
+    shape.width := 1;
+    area := shape.radius
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn strings() {
+        let text = "
+PROGRAM Strings;
+VAR
+    s, t, quoted, digits : STRING;
+    short : STRING[5];
+    n, code, position : INTEGER;
+    x : REAL;
+    less : BOOLEAN;
+
+BEGIN
+    s := 'Hello';
+    t := s + ', ' + 'World';
+    quoted := 'It''s';
+    short := t;
+    less := 'abc' < 'abd';
+
+    position := Pos('World', t);
+    n := Length(t) + StrToInt(' 42');
+    Delete(t, 6, 100);
+    Insert(' there', t, 6);
+    s := Concat(UpperCase(Copy(s, 1, 1)), LowerCase(Copy(s, 2, 10)), IntToStr(n));
+
+    Val('3.5', x, code);
+    Val('12x', n, code);
+    Str(position * 10, digits)
+END.";
+
+        let mut interpreter = interpret(text);
+        let string = |s: &str| Value::String(String::from(s));
+        assert_eq!(global(&mut interpreter, "t"), string("Hello there"));
+        assert_eq!(global(&mut interpreter, "quoted"), string("It's"));
+        assert_eq!(global(&mut interpreter, "short"), string("Hello"));
+        assert_eq!(global(&mut interpreter, "less"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "position"), Value::Integer(8));
+        assert_eq!(global(&mut interpreter, "s"), string("Hello54"));
+        assert_eq!(global(&mut interpreter, "x"), Value::Float(3.5));
+        assert_eq!(global(&mut interpreter, "n"), Value::Integer(54));
+        assert_eq!(global(&mut interpreter, "code"), Value::Integer(3));
+        assert_eq!(global(&mut interpreter, "digits"), string("80"));
+    }
+
+    #[test]
+    #[should_panic(expected = "'4x' is not a valid integer")]
+    fn invalid_integer_string() {
+        let text = "
+PROGRAM Invalid;
+VAR
+    n : INTEGER;
+
+BEGIN
+    n := StrToInt('4x')
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn chars() {
+        let text = "
+PROGRAM Chars;
+VAR
+    c, first, last, quote : CHAR;
+    s, greeting : STRING;
+    code, vowels : INTEGER;
+    counts : ARRAY['a'..'e'] OF INTEGER;
+    ordered : BOOLEAN;
+
+BEGIN
+    c := #65;
+    quote := '''';
+    code := Ord(c) + Ord(Succ('a')) - Ord(Pred('c'));
+    first := Chr(code);
+    last := Succ(Chr(Ord('y')));
+    ordered := ('a' < 'b') AND (first = 'A') AND ('ab' > 'a');
+    greeting := 'Hi' + '!'#33;
+
+    s := 'decade';
+    vowels := 0;
+    FOR c := 'a' TO 'e' DO
+        counts[c] := 0;
+    FOR code := 1 TO Length(s) DO
+        CASE s[code] OF
+            'a', 'e', 'i'..'o', 'u': vowels := vowels + 1
+        ELSE
+            counts[s[code]] := counts[s[code]] + 1
+        END;
+    s[1] := 'D'
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "first"), Value::Char('A'));
+        assert_eq!(global(&mut interpreter, "last"), Value::Char('z'));
+        assert_eq!(global(&mut interpreter, "quote"), Value::Char('\''));
+        assert_eq!(global(&mut interpreter, "ordered"), Value::Boolean(true));
+        assert_eq!(
+            global(&mut interpreter, "greeting"),
+            Value::String(String::from("Hi!!"))
+        );
+        assert_eq!(global(&mut interpreter, "vowels"), Value::Integer(3));
+        assert_eq!(
+            global(&mut interpreter, "counts"),
+            Value::Array(
+                97,
+                vec![
+                    Value::Integer(0),
+                    Value::Integer(0),
+                    Value::Integer(1),
+                    Value::Integer(2),
+                    Value::Integer(0)
+                ]
+            )
+        );
+        assert_eq!(
+            global(&mut interpreter, "s"),
+            Value::String(String::from("Decade"))
+        );
+    }
+
+    #[test]
+    fn write() {
+        let text = "
+PROGRAM Output;
+VAR
+    x : REAL;
+    s : STRING;
+
+BEGIN
+    x := 1.5;
+    WriteLn('x = ', x);
+    WriteLn(-x:10, '|', x:0:2, '|', x:8:3, '|');
+    WriteLn(42:5, 'ab':4, 'c':-3, TRUE, 1 < 0:6);
+    Write(7, ' ');
+    Str(x:4:1, s);
+    Write(s);
+    WriteLn;
+    Str(-0.125, s);
+    WriteLn(s)
+END.";
+
+        let (_, output) = run(text, "");
+        assert_eq!(
+            output,
+            "x =  1.500000000E+00\n\
+             -1.500E+00|1.50|   1.500|\n   42  abcTRUE FALSE\n\
+             7  1.5\n\
+             -1.250000000E-01\n"
+        );
+    }
+
+    #[test]
+    fn write_inexact_real() {
+        let text = "
+PROGRAM Output;
+BEGIN
+    WriteLn(3.14159);
+    WriteLn(1 / 3);
+    WriteLn(2 / 3:10)
+END.";
+
+        let (_, output) = run(text, "");
+        assert_eq!(output, " 3.141590000E+00\n 3.333333400E-01\n 6.667E-01\n");
+    }
+
+    #[test]
+    fn read() {
+        let text = "
+PROGRAM Input;
+VAR
+    a, b : INTEGER;
+    x : REAL;
+    c, d : CHAR;
+    name : STRING;
+    short : STRING[3];
+
+BEGIN
+    Read(a, b);
+    ReadLn(x);
+    { The end of a line reads as a blank, moving on to the next line }
+    Read(c, d);
+    ReadLn(name);
+    ReadLn(short)
+END.";
+
+        let (mut interpreter, _) = run(text, "  12\n  -7 2.5 ignored\nA\nJohn Smith\r\nabcdef");
+        assert_eq!(global(&mut interpreter, "a"), Value::Integer(12));
+        assert_eq!(global(&mut interpreter, "b"), Value::Integer(-7));
+        assert_eq!(global(&mut interpreter, "x"), Value::Float(2.5));
+        assert_eq!(global(&mut interpreter, "c"), Value::Char('A'));
+        assert_eq!(global(&mut interpreter, "d"), Value::Char(' '));
+        assert_eq!(
+            global(&mut interpreter, "name"),
+            Value::String(String::from("John Smith"))
+        );
+        assert_eq!(
+            global(&mut interpreter, "short"),
+            Value::String(String::from("abc"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "'x1' is not a valid number")]
+    fn read_invalid_number() {
+        let text = "
+PROGRAM Input;
+VAR
+    a : INTEGER;
+
+BEGIN
+    Read(a)
+END.";
+
+        run(text, "x1");
+    }
+
+    #[test]
+    fn math_functions() {
+        let text = "
+PROGRAM Maths;
+VAR
+    i, rounded, truncated, down, up : INTEGER;
+    x, root, trig, logs, p, area : REAL;
+    parity : BOOLEAN;
+
+BEGIN
+    i := Abs(-7) + Sqr(3);
+    x := Abs(-2.5) + Sqr(0.5);
+    root := Sqrt(16);
+    trig := Sin(0) + Cos(0) + ArcTan(0);
+    logs := Ln(Exp(2));
+    rounded := Round(2.5) + Round(-2.5);
+    truncated := Trunc(-3.7);
+    parity := Odd(i) OR Odd(-3);
+    p := Power(2, 10);
+    down := Floor(-1.5);
+    up := Ceil(1.2);
+    area := Pi * Sqr(2)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "i"), Value::Integer(16));
+        assert_eq!(global(&mut interpreter, "x"), Value::Float(2.75));
+        assert_eq!(global(&mut interpreter, "root"), Value::Float(4.0));
+        assert_eq!(global(&mut interpreter, "trig"), Value::Float(1.0));
+        assert_eq!(global(&mut interpreter, "logs"), Value::Float(2.0));
+        assert_eq!(global(&mut interpreter, "rounded"), Value::Integer(0));
+        assert_eq!(global(&mut interpreter, "truncated"), Value::Integer(-3));
+        assert_eq!(global(&mut interpreter, "parity"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "p"), Value::Float(1024.0));
+        assert_eq!(global(&mut interpreter, "down"), Value::Integer(-2));
+        assert_eq!(global(&mut interpreter, "up"), Value::Integer(2));
+        assert_eq!(
+            global(&mut interpreter, "area"),
+            Value::Float(std::f32::consts::PI * 4.0)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Floating point error in SQRT(-1)")]
+    fn sqrt_of_negative() {
+        let text = "
+PROGRAM Domain;
+VAR
+    x : REAL;
+
+BEGIN
+    x := Sqrt(-1)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Floating point error in LN(0)")]
+    fn ln_of_zero() {
+        let text = "
+PROGRAM Domain;
+VAR
+    x : REAL;
+
+BEGIN
+    x := Ln(0)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn ordinal_intrinsics() {
+        let text = "
+PROGRAM Ordinals;
+VAR
+    i, j, first, last, count : INTEGER;
+    c, top : CHAR;
+    b : BOOLEAN;
+    grid : ARRAY[1..3, 'a'..'d'] OF INTEGER;
+    pair : RECORD
+        x, y : REAL;
+        CASE flag : BOOLEAN OF
+            TRUE : (n : INTEGER);
+            FALSE : (s : STRING[7])
+    END;
+
+BEGIN
+    i := 1;
+    Inc(i);
+    Inc(i, 10);
+    Dec(i, 3);
+    j := 5;
+    Dec(j);
+    c := 'a';
+    Inc(c, 2);
+    b := Succ(FALSE);
+    first := Low(grid);
+    last := High(grid);
+    top := High(grid[1]);
+    count := High(INTEGER) - Ord(High(CHAR)) + Ord(Low(BOOLEAN));
+    i := i + SizeOf(grid) + SizeOf(pair) + SizeOf(BOOLEAN) + SizeOf(REAL)
+END.";
+
+        let mut interpreter = interpret(text);
+        // 9 + 48 + (4 + 4 + 1 + 8) + 1 + 4
+        assert_eq!(global(&mut interpreter, "i"), Value::Integer(79));
+        assert_eq!(global(&mut interpreter, "j"), Value::Integer(4));
+        assert_eq!(global(&mut interpreter, "c"), Value::Char('c'));
+        assert_eq!(global(&mut interpreter, "b"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "first"), Value::Integer(1));
+        assert_eq!(global(&mut interpreter, "last"), Value::Integer(3));
+        assert_eq!(global(&mut interpreter, "top"), Value::Char('d'));
+        assert_eq!(
+            global(&mut interpreter, "count"),
+            Value::Integer(i32::MAX - 255)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Range check error in SUCC(TRUE)")]
+    fn succ_past_end() {
+        let text = "
+PROGRAM Ends;
+VAR
+    b : BOOLEAN;
+
+BEGIN
+    b := Succ(TRUE)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range check error in INC(2147483647)")]
+    fn inc_overflow() {
+        let text = "
+PROGRAM Ends;
+VAR
+    i : INTEGER;
+
+BEGIN
+    i := High(INTEGER);
+    Inc(i)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn integer_operators() {
+        let text = "
+PROGRAM Bits;
+VAR
+    big, quotient, remainder, negative, masks, shifts, flipped : INTEGER;
+    x : REAL;
+    b : BOOLEAN;
+
+BEGIN
+    big := 16777217 * 2 + 1;
+    quotient := -7 DIV 2;
+    remainder := -7 MOD 3 + 7 MOD 3 * 10;
+    negative := 2 + 3 MOD 2 * 4;
+    masks := 12 AND 10 + 12 OR 3 XOR 1;
+    shifts := 1 SHL 4 + -16 SHR 28;
+    flipped := NOT 0;
+    x := 7 / 2;
+    b := TRUE XOR (1 < 2) OR FALSE
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "big"), Value::Integer(33554435));
+        assert_eq!(global(&mut interpreter, "quotient"), Value::Integer(-3));
+        assert_eq!(global(&mut interpreter, "remainder"), Value::Integer(12));
+        assert_eq!(global(&mut interpreter, "negative"), Value::Integer(6));
+        // (12 AND 10) + (12 OR 3) XOR 1 = (8 + 15) XOR 1
+        assert_eq!(global(&mut interpreter, "masks"), Value::Integer(22));
+        assert_eq!(global(&mut interpreter, "shifts"), Value::Integer(31));
+        assert_eq!(global(&mut interpreter, "flipped"), Value::Integer(-1));
+        assert_eq!(global(&mut interpreter, "x"), Value::Float(3.5));
+        assert_eq!(global(&mut interpreter, "b"), Value::Boolean(false));
+    }
+
+    #[test]
+    #[should_panic(expected = "Division by zero")]
+    fn div_by_zero() {
+        let text = "
+PROGRAM Zero;
+VAR
+    i, j : INTEGER;
+
+BEGIN
+    j := 0;
+    i := 1 DIV j
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Division by zero")]
+    fn mod_by_zero() {
+        let text = "
+PROGRAM Zero;
+VAR
+    i, j : INTEGER;
+
+BEGIN
+    j := 0;
+    i := 1 MOD j
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Integer overflow")]
+    fn integer_overflow() {
+        let text = "
+PROGRAM Overflow;
+VAR
+    i : INTEGER;
+
+BEGIN
+    i := 2147483647;
+    i := i + 1
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn constants() {
+        let text = "
+PROGRAM Constants;
+CONST
+    Max = 10;
+    Half = Max DIV 2;
+    Greeting = 'Hi';
+    Letter = Chr(Ord('a') + 2);
+    Limit : REAL = 5;
+VAR
+    a : ARRAY[1..Max] OF INTEGER;
+    n : INTEGER = Half + 1;
+    s : STRING;
+    x : REAL;
+CONST
+    Top = High(a) - 1;
+
+BEGIN
+    a[Max] := Top;
+    CASE n OF
+        Half : s := 'half';
+        Half + 1 : s := Greeting + Letter
+    END;
+    x := Limit / 2
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(
+            global(&mut interpreter, "a"),
+            Value::Array(1, [vec![Value::None; 9], vec![Value::Integer(9)]].concat())
+        );
+        assert_eq!(global(&mut interpreter, "n"), Value::Integer(6));
+        assert_eq!(
+            global(&mut interpreter, "s"),
+            Value::String(String::from("Hic"))
+        );
+        assert_eq!(global(&mut interpreter, "x"), Value::Float(2.5));
+    }
+
+    #[test]
+    fn type_declarations() {
+        let text = "
+PROGRAM Types;
+TYPE
+    Small = 1..3;
+    Count = INTEGER;
+    Vector = ARRAY[Small] OF REAL;
+    Point = RECORD
+        x, y : REAL
+    END;
+VAR
+    v : Vector;
+    p : Point;
+    total : Count;
+
+PROCEDURE Fill(VAR v : Vector; VAR p : Point);
+TYPE
+    Index = Small;
+VAR
+    i : Index;
+BEGIN
+    FOR i := Low(v) TO High(v) DO
+        v[i] := i * p.x
+END;
+
+BEGIN
+    p.x := 1.5;
+    p.y := 0;
+    Fill(v, p);
+    total := SizeOf(Vector) + SizeOf(Small)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(
+            global(&mut interpreter, "v"),
+            Value::Array(
+                1,
+                vec![Value::Float(1.5), Value::Float(3.0), Value::Float(4.5)]
+            )
+        );
+        assert_eq!(global(&mut interpreter, "total"), Value::Integer(13));
+    }
+
+    #[test]
+    fn enumerations() {
+        let text = "
+PROGRAM Colours;
+TYPE
+    Colour = (Red, Green, Blue);
+    Warm = Red..Green;
+VAR
+    c : Colour;
+    w : Warm;
+    counts : ARRAY[Colour] OF INTEGER;
+    letter : 'a'..'z';
+    last : Colour;
+    b : BOOLEAN;
+
+BEGIN
+    FOR c := Red TO Blue DO
+        counts[c] := Ord(c) * 10;
+    w := Succ(Red);
+    last := Pred(High(Colour));
+    Inc(last);
+    letter := 'q';
+    b := (w < last) AND (Low(counts) = Red);
+    CASE w OF
+        Red : WriteLn('red');
+        Green, Blue : WriteLn(w, ' ', last:6)
+    END
+END.";
+
+        let (mut interpreter, output) = run(text, "");
+        assert_eq!(output, "Green   Blue\n");
+        assert_eq!(
+            global(&mut interpreter, "counts"),
+            Value::Array(
+                0,
+                vec![Value::Integer(0), Value::Integer(10), Value::Integer(20)]
+            )
+        );
+        assert_eq!(global(&mut interpreter, "letter"), Value::Char('q'));
+        assert_eq!(global(&mut interpreter, "b"), Value::Boolean(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "Value Blue out of range Red..Green")]
+    fn enum_subrange_check() {
+        let text = "
+PROGRAM Colours;
+TYPE
+    Colour = (Red, Green, Blue);
+VAR
+    w : Red..Green;
+
+BEGIN
+    w := Green;
+    Inc(w)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Value 11 out of range 1..10")]
+    fn subrange_check() {
+        let text = "
+PROGRAM Ranges;
+VAR
+    i : INTEGER;
+    small : 1..10;
+
+BEGIN
+    i := 11;
+    small := i
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn sets() {
+        let text = "
+PROGRAM Sets;
+TYPE
+    Colour = (Red, Green, Blue);
+    Colours = SET OF Colour;
+CONST
+    Vowels = ['a', 'e', 'i', 'o', 'u'];
+VAR
+    digits, odds, evens, mixed : SET OF 0..9;
+    warm : Colours;
+    letters : SET OF CHAR;
+    c : CHAR;
+    count, size : INTEGER;
+    found, subset, superset, equal : BOOLEAN;
+
+BEGIN
+    digits := [0..9];
+    odds := [1, 3, 5..5, 7, 9];
+    evens := digits - odds;
+    mixed := odds * [1..4] + [8];
+    warm := [Red] + [];
+    count := 0;
+    FOR c := 'a' TO 'z' DO
+        IF c IN Vowels THEN
+            count := count + 1;
+    found := (Green IN warm) OR (4 IN evens) AND NOT (4 IN odds);
+    subset := odds <= digits;
+    superset := odds >= digits;
+    equal := evens + odds = digits;
+    size := SizeOf(warm) + SizeOf(letters)
+END.";
+
+        let mut interpreter = interpret(text);
+        let set = |elements: &[i32]| Value::Set(elements.iter().copied().collect());
+        assert_eq!(global(&mut interpreter, "evens"), set(&[0, 2, 4, 6, 8]));
+        assert_eq!(global(&mut interpreter, "mixed"), set(&[1, 3, 8]));
+        assert_eq!(global(&mut interpreter, "warm"), set(&[0]));
+        assert_eq!(global(&mut interpreter, "count"), Value::Integer(5));
+        assert_eq!(global(&mut interpreter, "found"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "subset"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "superset"), Value::Boolean(false));
+        assert_eq!(global(&mut interpreter, "equal"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "size"), Value::Integer(36));
+    }
+
+    #[test]
+    #[should_panic(expected = "Set element 300 out of range 0..255")]
+    fn set_element_out_of_range() {
+        let text = "
+PROGRAM Sets;
+VAR
+    i : INTEGER;
+    b : BOOLEAN;
+
+BEGIN
+    i := 300;
+    b := 1 IN [i]
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn linked_list() {
+        let text = "
+PROGRAM Lists;
+TYPE
+    PNode = ^TNode;
+    TNode = RECORD
+        value : INTEGER;
+        next : PNode
+    END;
+VAR
+    head, node : PNode;
+    counter : ^INTEGER;
+    i, sum : INTEGER;
+    empty : BOOLEAN;
+
+PROCEDURE Push(VAR list : PNode; value : INTEGER);
+VAR
+    node : PNode;
+BEGIN
+    New(node);
+    node^.value := value;
+    node^.next := list;
+    list := node
+END;
+
+BEGIN
+    head := NIL;
+    empty := head = NIL;
+    FOR i := 1 TO 4 DO
+        Push(head, i * 10);
+    head^.next^.value := head^.next^.value + 5;
+    New(counter);
+    counter^ := 0;
+    sum := 0;
+    node := head;
+    WHILE node <> NIL DO
+    BEGIN
+        sum := sum + node^.value;
+        Inc(counter^);
+        node := node^.next
+    END;
+    i := counter^;
+    Dispose(counter);
+    WHILE head <> NIL DO
+    BEGIN
+        node := head;
+        head := head^.next;
+        Dispose(node)
+    END
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "empty"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "sum"), Value::Integer(105));
+        assert_eq!(global(&mut interpreter, "i"), Value::Integer(4));
+        assert_eq!(global(&mut interpreter, "head"), Value::Pointer(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "Dereference of NIL pointer in p^ at 8:7")]
+    fn nil_dereference() {
+        let text = "
+PROGRAM Pointers;
+VAR
+    p : ^INTEGER;
+
+BEGIN
+    p := NIL;
+    p^ := 1
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Dereference of disposed pointer in q^ at 10:7")]
+    fn use_after_dispose() {
+        let text = "
+PROGRAM Pointers;
+VAR
+    p, q : ^INTEGER;
+
+BEGIN
+    New(p);
+    q := p;
+    Dispose(p);
+    q^ := 1
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Dereference of disposed pointer in p^ at 15:13")]
+    fn var_parameter_after_dispose() {
+        let text = "
+PROGRAM Pointers;
+VAR
+    p : ^INTEGER;
+
+PROCEDURE Reset(VAR n : INTEGER);
+BEGIN
+    Dispose(p);
+    n := 0
+END;
+
+BEGIN
+    New(p);
+    p^ := 1;
+    Reset(p^)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Dereference of disposed pointer in p^ at 15:12")]
+    fn read_var_parameter_after_dispose() {
+        let text = "
+PROGRAM Pointers;
+VAR
+    p : ^INTEGER;
+
+PROCEDURE Show(VAR n : INTEGER);
+BEGIN
+    Dispose(p);
+    WriteLn(n)
+END;
+
+BEGIN
+    New(p);
+    p^ := 1;
+    Show(p^)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pointer disposed twice at 9:12")]
+    fn double_dispose() {
+        let text = "
+PROGRAM Pointers;
+VAR
+    p : ^INTEGER;
+
+BEGIN
+    New(p);
+    Dispose(p);
+    Dispose(p)
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn leak_report() {
+        let text = "
+PROGRAM Leaks;
+VAR
+    p, q : ^INTEGER;
+
+BEGIN
+    New(p);
+    New(q);
+    Dispose(p);
+    new(p);
+    WriteLn('done')
+END.";
+
+        let (_, output) = run(text, "");
+        assert_eq!(
+            output,
+            "done\n2 heap variable(s) not disposed:\n  ^1 created by New at 8:8\n  ^2 created by new at 10:8\n"
+        );
+    }
+
+    #[test]
+    fn dynamic_arrays() {
+        let text = "
+PROGRAM Dynamic;
+TYPE
+    TRow = ARRAY OF INTEGER;
+VAR
+    a, b, c : TRow;
+    triangle : ARRAY OF TRow;
+    i, j, total, first, last, kept, shared, copied, cleared, rows : INTEGER;
+
+PROCEDURE Clear(row : TRow);
+BEGIN
+    row[0] := 0
+END;
+
+BEGIN
+    SetLength(a, 3);
+    FOR i := 0 TO High(a) DO
+        a[i] := i + 1;
+    SetLength(a, 5);
+    kept := a[2];
+    first := Low(a);
+    last := High(a);
+
+    b := a;
+    b[1] := 20;
+    shared := a[1];
+    c := a;
+    SetLength(c, 2);
+    c[0] := 10;
+    copied := a[0];
+    Clear(a);
+    cleared := b[0];
+
+    SetLength(triangle, 4);
+    FOR i := 0 TO 3 DO
+    BEGIN
+        SetLength(triangle[i], i + 1);
+        FOR j := 0 TO i DO
+            triangle[i, j] := j
+    END;
+    total := 0;
+    FOR i := 0 TO High(triangle) DO
+        FOR j := 0 TO High(triangle[i]) DO
+            total := total + triangle[i][j];
+    rows := Length(triangle) * 100 + Length(triangle[2])
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "kept"), Value::Integer(3));
+        assert_eq!(global(&mut interpreter, "first"), Value::Integer(0));
+        assert_eq!(global(&mut interpreter, "last"), Value::Integer(4));
+        assert_eq!(global(&mut interpreter, "shared"), Value::Integer(20));
+        assert_eq!(global(&mut interpreter, "copied"), Value::Integer(1));
+        assert_eq!(global(&mut interpreter, "total"), Value::Integer(10));
+        assert_eq!(global(&mut interpreter, "rows"), Value::Integer(403));
+        assert_eq!(global(&mut interpreter, "cleared"), Value::Integer(0));
+    }
+
+    #[test]
+    fn set_length_clears_new_elements() {
+        let text = "
+PROGRAM Cleared;
+TYPE
+    TPoint = RECORD
+        x, y : REAL
+    END;
+VAR
+    numbers : ARRAY OF INTEGER;
+    names : ARRAY OF STRING;
+    flags : ARRAY OF BOOLEAN;
+    points : ARRAY OF TPoint;
+    number, added : INTEGER;
+    name : STRING;
+    flag : BOOLEAN;
+    y : REAL;
+
+BEGIN
+    SetLength(numbers, 1);
+    numbers[0] := 5;
+    SetLength(numbers, 3);
+    number := numbers[0];
+    added := numbers[2];
+    SetLength(names, 2);
+    name := names[1];
+    SetLength(flags, 1);
+    flag := flags[0];
+    SetLength(points, 2);
+    y := points[1].y
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "number"), Value::Integer(5));
+        assert_eq!(global(&mut interpreter, "added"), Value::Integer(0));
+        assert_eq!(
+            global(&mut interpreter, "name"),
+            Value::String(String::new())
+        );
+        assert_eq!(global(&mut interpreter, "flag"), Value::Boolean(false));
+        assert_eq!(global(&mut interpreter, "y"), Value::Float(0.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 2 out of bounds 0..1")]
+    fn dynamic_array_bounds() {
+        let text = "
+PROGRAM Dynamic;
+VAR
+    a : ARRAY OF CHAR;
+
+BEGIN
+    SetLength(a, 2);
+    a[2] := 'x'
+END.";
+
+        interpret(text);
+    }
+
+    #[test]
+    fn array_parameters() {
+        let text = "
+PROGRAM Arrays;
+TYPE
+    Letter = 'a'..'z';
+VAR
+    numbers : ARRAY[1..5] OF INTEGER;
+    dynamic : ARRAY OF INTEGER;
+    counts : ARRAY['c'..'e'] OF INTEGER;
+    i, fixedSum, dynamicSum, highest, last : INTEGER;
+    first : CHAR;
+
+FUNCTION Sum(values : ARRAY OF INTEGER) : INTEGER;
+VAR
+    i, total : INTEGER;
+BEGIN
+    total := 0;
+    FOR i := Low(values) TO High(values) DO
+        total := total + values[i];
+    values[0] := 0;
+    Sum := total
+END;
+
+PROCEDURE Sort(VAR a : ARRAY[lo..hi : INTEGER] OF INTEGER);
+VAR
+    i, j, t : INTEGER;
+BEGIN
+    FOR i := lo TO hi - 1 DO
+        FOR j := i + 1 TO hi DO
+            IF a[j] < a[i] THEN
+            BEGIN
+                t := a[i];
+                a[i] := a[j];
+                a[j] := t
+            END
+END;
+
+FUNCTION Largest(VAR a : ARRAY OF INTEGER) : INTEGER;
+BEGIN
+    Largest := a[High(a)]
+END;
+
+PROCEDURE Bounds(a : ARRAY[l..h : Letter] OF INTEGER; VAR lowest : CHAR; VAR count : INTEGER);
+BEGIN
+    lowest := l;
+    count := Ord(High(a)) - Ord(Low(a)) + Length(a)
+END;
+
+BEGIN
+    numbers[1] := 4;
+    numbers[2] := 1;
+    numbers[3] := 5;
+    numbers[4] := 2;
+    numbers[5] := 3;
+    fixedSum := Sum(numbers);
+    SetLength(dynamic, 3);
+    FOR i := 0 TO 2 DO
+        dynamic[i] := i * 10 + 1;
+    dynamicSum := Sum(dynamic) + dynamic[0];
+    Sort(numbers);
+    highest := Largest(numbers);
+    Bounds(counts, first, last)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "fixedsum"), Value::Integer(15));
+        assert_eq!(global(&mut interpreter, "dynamicsum"), Value::Integer(34));
+        assert_eq!(
+            global(&mut interpreter, "numbers"),
+            Value::Array(1, (1..=5).map(Value::Integer).collect())
+        );
+        assert_eq!(global(&mut interpreter, "highest"), Value::Integer(5));
+        assert_eq!(global(&mut interpreter, "first"), Value::Char('c'));
+        assert_eq!(global(&mut interpreter, "last"), Value::Integer(5));
+    }
+
+    #[test]
+    fn procedural_parameters() {
+        let text = "
+PROGRAM Callbacks;
+TYPE
+    TCmp = FUNCTION(a, b : INTEGER) : BOOLEAN;
+VAR
+    area, root, count : INTEGER;
+    cmp : TCmp;
+    ascending, descending : BOOLEAN;
+
+FUNCTION Integrate(FUNCTION f(x : REAL) : REAL; a, b : REAL; n : INTEGER) : REAL;
+VAR
+    i : INTEGER;
+    h, sum : REAL;
+BEGIN
+    h := (b - a) / n;
+    sum := 0;
+    FOR i := 0 TO n - 1 DO
+        sum := sum + f(a + (i + 0.5) * h);
+    Integrate := sum * h
+END;
+
+FUNCTION Bisect(FUNCTION f(x : REAL) : REAL; a, b : REAL) : REAL;
+VAR
+    middle : REAL;
+BEGIN
+    WHILE b - a > 0.0001 DO
+    BEGIN
+        middle := (a + b) / 2;
+        IF f(a) * f(middle) <= 0 THEN
+            b := middle
+        ELSE
+            a := middle
+    END;
+    Bisect := (a + b) / 2
+END;
+
+FUNCTION Square(x : REAL) : REAL;
+BEGIN
+    Square := x * x
+END;
+
+FUNCTION SquareRoot(k : REAL) : REAL;
+
+    FUNCTION Residual(x : REAL) : REAL;
+    BEGIN
+        Residual := Square(x) - k
+    END;
+
+BEGIN
+    SquareRoot := Bisect(Residual, 0, k)
+END;
+
+PROCEDURE Repeat3(PROCEDURE action);
+BEGIN
+    action;
+    action;
+    action
+END;
+
+PROCEDURE Tick;
+BEGIN
+    count := count + 1
+END;
+
+FUNCTION Less(a, b : INTEGER) : BOOLEAN;
+BEGIN
+    Less := a < b
+END;
+
+FUNCTION Greater(x, y : INTEGER) : BOOLEAN;
+BEGIN
+    Greater := x > y
+END;
+
+FUNCTION Sorted(a, b, c : INTEGER; inOrder : TCmp) : BOOLEAN;
+BEGIN
+    Sorted := inOrder(a, b) AND inOrder(b, c)
+END;
+
+BEGIN
+    area := Round(Integrate(Square, 0, 1, 100) * 1000);
+    root := Round(SquareRoot(2) * 1000);
+    count := 0;
+    Repeat3(Tick);
+    cmp := Less;
+    ascending := Sorted(1, 2, 3, cmp);
+    cmp := Greater;
+    descending := cmp(3, 2) AND Sorted(1, 2, 3, Greater)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "area"), Value::Integer(333));
+        assert_eq!(global(&mut interpreter, "root"), Value::Integer(1414));
+        assert_eq!(global(&mut interpreter, "count"), Value::Integer(3));
+        assert_eq!(global(&mut interpreter, "ascending"), Value::Boolean(true));
+        assert_eq!(
+            global(&mut interpreter, "descending"),
+            Value::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn routines_taking_routines() {
+        let text = "
+PROGRAM Higher;
+VAR
+    total : REAL;
+
+FUNCTION Double(x : REAL) : REAL;
+BEGIN
+    Double := 2 * x
+END;
+
+FUNCTION Sum(FUNCTION f(x : REAL) : REAL) : REAL;
+BEGIN
+    Sum := f(1) + f(2)
+END;
+
+FUNCTION Apply(FUNCTION combine(FUNCTION g(y : REAL) : REAL) : REAL) : REAL;
+BEGIN
+    Apply := combine(Double)
+END;
+
+BEGIN
+    total := Apply(Sum)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "total"), Value::Float(6.0));
+    }
+
+    #[test]
+    fn forward_declarations() {
+        let text = "
+PROGRAM Forward;
+VAR
+    even, odd7 : BOOLEAN;
+    steps : INTEGER;
+
+FUNCTION IsOdd(n : INTEGER) : BOOLEAN; FORWARD;
+PROCEDURE Collatz(n : INTEGER); forward;
+
+FUNCTION IsEven(n : INTEGER) : BOOLEAN;
+BEGIN
+    IF n = 0 THEN
+        IsEven := TRUE
+    ELSE
+        IsEven := IsOdd(n - 1)
+END;
+
+FUNCTION IsOdd;
+BEGIN
+    IF n = 0 THEN
+        IsOdd := FALSE
+    ELSE
+        IsOdd := IsEven(n - 1)
+END;
+
+PROCEDURE Halve(n : INTEGER);
+BEGIN
+    Collatz(n DIV 2)
+END;
+
+PROCEDURE Collatz(n : INTEGER);
+BEGIN
+    IF n > 1 THEN
+    BEGIN
+        steps := steps + 1;
+        IF n MOD 2 = 0 THEN
+            Halve(n)
+        ELSE
+            Collatz(3 * n + 1)
+    END
+END;
+
+BEGIN
+    even := IsEven(10);
+    odd7 := IsOdd(7);
+    steps := 0;
+    Collatz(6)
+END.";
+
+        let mut interpreter = interpret(text);
+        assert_eq!(global(&mut interpreter, "even"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "odd7"), Value::Boolean(true));
+        assert_eq!(global(&mut interpreter, "steps"), Value::Integer(8));
+    }
+
+    #[test]
+    fn result_as_identifier() {
+        let text = "
+PROGRAM Results;
+VAR
+    doubled, tripled : INTEGER;
+
+FUNCTION Double(result : INTEGER) : INTEGER;
+BEGIN
+    Double := result * 2
+END;
+
+FUNCTION Triple(n : INTEGER) : INTEGER;
+VAR
+    result : INTEGER;
+BEGIN
+    result := n * 3;
+    Triple := result
+END;
+
+BEGIN
+    doubled := Double(4);
+    tripled := Triple(5)
+END.";
+
+        let mut interpreter = run_as(Dialect::Iso, text, "").0;
+        assert_eq!(global(&mut interpreter, "doubled"), Value::Integer(8));
+        assert_eq!(global(&mut interpreter, "tripled"), Value::Integer(15));
     }
 }
@@ -6,11 +6,44 @@ const RESERVED_KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "BEGIN" => TokenType::Begin,
     "END" => TokenType::End,
     "DIV" => TokenType::IntegerDiv,
+    "MOD" => TokenType::Mod,
+    "SHL" => TokenType::Shl,
+    "SHR" => TokenType::Shr,
+    "XOR" => TokenType::Xor,
     "PROGRAM" => TokenType::Program,
     "INTEGER" => TokenType::Integer,
     "REAL" => TokenType::Real,
+    "BOOLEAN" => TokenType::Boolean,
+    "STRING" => TokenType::String,
+    "CHAR" => TokenType::Char,
+    "ARRAY" => TokenType::Array,
+    "RECORD" => TokenType::Record,
+    "SET" => TokenType::Set,
+    "IN" => TokenType::In,
+    "NIL" => TokenType::Nil,
+    "TRUE" => TokenType::True,
+    "FALSE" => TokenType::False,
+    "AND" => TokenType::And,
+    "OR" => TokenType::Or,
+    "NOT" => TokenType::Not,
+    "CONST" => TokenType::Const,
+    "TYPE" => TokenType::Type,
     "VAR" => TokenType::Var,
-    "PROCEDURE" => TokenType::Procedure
+    "PROCEDURE" => TokenType::Procedure,
+    "FUNCTION" => TokenType::Function,
+    "IF" => TokenType::If,
+    "THEN" => TokenType::Then,
+    "ELSE" => TokenType::Else,
+    "WHILE" => TokenType::While,
+    "DO" => TokenType::Do,
+    "REPEAT" => TokenType::Repeat,
+    "UNTIL" => TokenType::Until,
+    "FOR" => TokenType::For,
+    "TO" => TokenType::To,
+    "DOWNTO" => TokenType::Downto,
+    "CASE" => TokenType::Case,
+    "OF" => TokenType::Of,
+    "OTHERWISE" => TokenType::Otherwise
 };
 
 pub struct Lexer {
@@ -35,7 +68,7 @@ impl Lexer {
     fn error(&self) {
         LexerError::new(format!(
             "Lexer error on `{}` line: {} column: {}",
-            self.current_char.clone().unwrap(),
+            self.current_char.unwrap(),
             self.lineno,
             self.column
         ))
@@ -69,6 +102,78 @@ impl Lexer {
         self.advance();
     }
 
+    /// A quoted piece of a literal, in which `''` stands for a single quote
+    fn quoted(&mut self, result: &mut String) {
+        let (lineno, column) = (self.lineno, self.column);
+        self.advance();
+        loop {
+            match self.current_char {
+                Some('\'') if self.peek() == Some('\'') => {
+                    result.push('\'');
+                    self.advance();
+                    self.advance();
+                }
+                Some('\'') => {
+                    self.advance();
+                    break;
+                }
+                Some(c) => {
+                    result.push(c);
+                    self.advance();
+                }
+                None => LexerError::new(format!(
+                    "Unterminated string on line: {} column: {}",
+                    lineno, column
+                ))
+                .throw(),
+            }
+        }
+    }
+
+    /// A character code like `#65`
+    fn character_code(&mut self, result: &mut String) {
+        self.advance();
+        let mut code = String::new();
+        while let Some(n) = self.current_char.filter(|c| c.is_ascii_digit()) {
+            code.push(n);
+            self.advance();
+        }
+        match code.parse().ok().and_then(char::from_u32) {
+            Some(c) => result.push(c),
+            None => self.error(),
+        }
+    }
+
+    /// A string or character literal, made of quoted pieces and character
+    /// codes written next to each other, like `'Hello'#10`. A literal of
+    /// exactly one character is a CHAR.
+    fn string(&mut self) -> Token {
+        let mut result = String::new();
+        loop {
+            match self.current_char {
+                Some('\'') => self.quoted(&mut result),
+                Some('#') => self.character_code(&mut result),
+                _ => break,
+            }
+        }
+
+        let mut chars = result.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Token::new(
+                TokenType::CharConst,
+                Value::Char(c),
+                self.lineno,
+                self.column,
+            ),
+            _ => Token::new(
+                TokenType::StringConst,
+                Value::String(result),
+                self.lineno,
+                self.column,
+            ),
+        }
+    }
+
     fn number(&mut self) -> Token {
         let mut result = String::new();
         while let Some(n) = self.current_char.filter(|c| c.is_numeric()) {
@@ -76,7 +181,8 @@ impl Lexer {
             self.advance();
         }
 
-        if let Some('.') = self.current_char {
+        // `1..5` is a range, not the real number `1.`
+        if let (Some('.'), false) = (self.current_char, self.peek() == Some('.')) {
             result.push('.');
             self.advance();
 
@@ -147,6 +253,26 @@ impl Lexer {
                     );
                 }
 
+                '[' => {
+                    self.advance();
+                    return Token::new(
+                        TokenType::LeftBracket,
+                        Value::Char(c),
+                        self.lineno,
+                        self.column,
+                    );
+                }
+
+                ']' => {
+                    self.advance();
+                    return Token::new(
+                        TokenType::RightBracket,
+                        Value::Char(c),
+                        self.lineno,
+                        self.column,
+                    );
+                }
+
                 ')' => {
                     self.advance();
                     return Token::new(
@@ -178,16 +304,85 @@ impl Lexer {
                     }
                 }
 
+                '=' => {
+                    self.advance();
+                    return Token::new(TokenType::Equal, Value::Char(c), self.lineno, self.column);
+                }
+
+                '<' => {
+                    self.advance();
+                    return match self.current_char {
+                        Some('>') => {
+                            self.advance();
+                            Token::new(
+                                TokenType::NotEqual,
+                                Value::String(String::from("<>")),
+                                self.lineno,
+                                self.column,
+                            )
+                        }
+                        Some('=') => {
+                            self.advance();
+                            Token::new(
+                                TokenType::LessEqual,
+                                Value::String(String::from("<=")),
+                                self.lineno,
+                                self.column,
+                            )
+                        }
+                        _ => Token::new(
+                            TokenType::LessThan,
+                            Value::Char(c),
+                            self.lineno,
+                            self.column,
+                        ),
+                    };
+                }
+
+                '>' => {
+                    self.advance();
+                    if let Some('=') = self.current_char {
+                        self.advance();
+                        return Token::new(
+                            TokenType::GreaterEqual,
+                            Value::String(String::from(">=")),
+                            self.lineno,
+                            self.column,
+                        );
+                    }
+                    return Token::new(
+                        TokenType::GreaterThan,
+                        Value::Char(c),
+                        self.lineno,
+                        self.column,
+                    );
+                }
+
                 ';' => {
                     self.advance();
                     return Token::new(TokenType::Semi, Value::Char(c), self.lineno, self.column);
                 }
 
                 '.' => {
+                    if let Some('.') = self.peek() {
+                        self.advance();
+                        self.advance();
+                        return Token::new(
+                            TokenType::Range,
+                            Value::String(String::from("..")),
+                            self.lineno,
+                            self.column,
+                        );
+                    }
                     self.advance();
                     return Token::new(TokenType::Dot, Value::Char(c), self.lineno, self.column);
                 }
 
+                '^' => {
+                    self.advance();
+                    return Token::new(TokenType::Caret, Value::Char(c), self.lineno, self.column);
+                }
+
                 '{' => {
                     self.advance();
                     self.skip_comment();
@@ -199,6 +394,8 @@ impl Lexer {
                     return Token::new(TokenType::Comma, Value::Char(c), self.lineno, self.column);
                 }
 
+                '\'' | '#' => return self.string(),
+
                 c => {
                     if c.is_alphabetic() || c == '_' {
                         return self.id();
@@ -214,7 +411,7 @@ impl Lexer {
     }
 
     fn peek(&self) -> Option<char> {
-        if self.pos > self.text.len() {
+        if self.pos + 1 >= self.text.len() {
             None
         } else {
             Some(self.text.as_bytes()[self.pos + 1] as char)
@@ -238,7 +435,14 @@ impl Lexer {
                 self.lineno,
                 self.column,
             ),
-            |t| Token::new(t.clone(), Value::String(result), self.lineno, self.column),
+            |t| {
+                let value = match t {
+                    TokenType::True => Value::Boolean(true),
+                    TokenType::False => Value::Boolean(false),
+                    _ => Value::String(result),
+                };
+                Token::new(t.clone(), value, self.lineno, self.column)
+            },
         )
     }
 }
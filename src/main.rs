@@ -1,5 +1,7 @@
 mod ast;
+mod builtins;
 mod error;
+mod input;
 mod interpreter;
 mod lexer;
 mod parser;
@@ -7,6 +9,7 @@ mod semantic_analyzer;
 mod symbols;
 mod tokens;
 
+use crate::builtins::Dialect;
 use crate::interpreter::{Interpreter, NodeVisitor};
 use crate::lexer::Lexer;
 use crate::parser::Parser;
@@ -14,17 +17,38 @@ use crate::semantic_analyzer::SemanticAnalyzer;
 use std::{env, fs};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<_> = env::args().collect();
-    let source = fs::read_to_string(args[1].clone())?;
+    // Like Free Pascal, `-Mtp` or `-Mfpc` turn on the Turbo extensions
+    let mut dialect = Dialect::Iso;
+    let mut range_checks = false;
+    let mut heap_checks = false;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-Miso" => dialect = Dialect::Iso,
+            "-Mtp" | "-Mfpc" | "-Mobjfpc" | "-Mdelphi" => dialect = Dialect::Turbo,
+            "-Cr" => range_checks = true,
+            // Free Pascal's switch for its heaptrc unit
+            "-gh" => heap_checks = true,
+            _ => path = Some(arg),
+        }
+    }
+    let source =
+        fs::read_to_string(path.ok_or("Usage: interpreter [-Miso|-Mfpc] [-Cr] [-gh] FILE")?)?;
 
     let lexer = Lexer::new(source);
     let mut parser = Parser::new(lexer);
     let mut tree = parser.parse();
 
-    let mut semantic_analyzer = SemanticAnalyzer::new();
+    let mut semantic_analyzer = SemanticAnalyzer::new(dialect);
     semantic_analyzer.visit(&mut tree);
 
     let mut interpreter = Interpreter::new();
+    if range_checks {
+        interpreter.enable_range_checks();
+    }
+    if heap_checks {
+        interpreter.enable_heap_checks();
+    }
     let _result = interpreter.visit(&mut tree);
 
     Ok(())
@@ -1,7 +1,7 @@
 use crate::ast::*;
 use crate::error::{ErrorCode, ParserError};
 use crate::lexer::Lexer;
-use crate::tokens::{Token, TokenType};
+use crate::tokens::{Token, TokenType, Value};
 
 pub struct Parser {
     lexer: Lexer,
@@ -51,21 +51,87 @@ impl Parser {
                 self.eat(TokenType::RealConst);
                 Node::Num(token.value)
             }
+            TokenType::StringConst => {
+                self.eat(TokenType::StringConst);
+                Node::Num(token.value)
+            }
+            TokenType::CharConst => {
+                self.eat(TokenType::CharConst);
+                Node::Num(token.value)
+            }
+            TokenType::True => {
+                self.eat(TokenType::True);
+                Node::Num(token.value)
+            }
+            TokenType::False => {
+                self.eat(TokenType::False);
+                Node::Num(token.value)
+            }
+            TokenType::Not => {
+                self.eat(TokenType::Not);
+                Node::UnaryOp(token, Box::new(self.factor()))
+            }
             TokenType::LeftParen => {
                 self.eat(TokenType::LeftParen);
                 let node = self.expr();
                 self.eat(TokenType::RightParen);
                 node
             }
-            _ => Node::Var(self.variable()),
+            TokenType::LeftBracket => self.set_constructor(),
+            TokenType::Nil => {
+                self.eat(TokenType::Nil);
+                Node::Num(Value::Pointer(None))
+            }
+            // A type name passed to Low, High or SizeOf
+            TokenType::Integer
+            | TokenType::Real
+            | TokenType::Boolean
+            | TokenType::Char
+            | TokenType::String => {
+                self.eat(token.type_.clone());
+                Node::Var(Var::new(token))
+            }
+            _ => {
+                let var = self.variable();
+                if let TokenType::LeftParen = self.current_token.as_ref().unwrap().type_ {
+                    Node::ProcedureCall(ProcedureCall::new(
+                        var.value.expect_string(),
+                        self.actual_parameters(),
+                        var.token,
+                    ))
+                } else {
+                    Node::Var(var)
+                }
+            }
         }
     }
 
+    /// `[a, b..c]`, where the elements may also be ranges
+    fn set_constructor(&mut self) -> Node {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::LeftBracket);
+        let mut elements = Vec::new();
+        if self.current_token.as_ref().unwrap().type_ != TokenType::RightBracket {
+            elements.push(self.case_label());
+            while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
+                self.eat(TokenType::Comma);
+                elements.push(self.case_label());
+            }
+        }
+        self.eat(TokenType::RightBracket);
+        Node::Set(token, elements)
+    }
+
     fn term(&mut self) -> Node {
         let mut node = self.factor();
 
-        while let TokenType::Mul | TokenType::IntegerDiv | TokenType::FloatDiv =
-            self.current_token.as_ref().unwrap().type_
+        while let TokenType::Mul
+        | TokenType::IntegerDiv
+        | TokenType::FloatDiv
+        | TokenType::Mod
+        | TokenType::Shl
+        | TokenType::Shr
+        | TokenType::And = self.current_token.as_ref().unwrap().type_
         {
             let token = self.current_token.clone().unwrap();
             self.eat(token.clone().type_);
@@ -74,47 +140,220 @@ impl Parser {
         node
     }
 
-    fn expr(&mut self) -> Node {
+    fn simple_expr(&mut self) -> Node {
         let mut node = self.term();
 
-        while let TokenType::Plus | TokenType::Minus = self.current_token.as_ref().unwrap().type_ {
+        while let TokenType::Plus | TokenType::Minus | TokenType::Or | TokenType::Xor =
+            self.current_token.as_ref().unwrap().type_
+        {
             let token = self.current_token.clone().unwrap();
-            match token.type_ {
-                TokenType::Plus => self.eat(TokenType::Plus),
-                TokenType::Minus => self.eat(TokenType::Minus),
-                _ => unimplemented!(),
-            }
+            self.eat(token.clone().type_);
             node = Node::BinOp(Box::new(node), token, Box::new(self.term()));
         }
         node
     }
 
+    fn expr(&mut self) -> Node {
+        let node = self.simple_expr();
+
+        match self.current_token.as_ref().unwrap().type_ {
+            TokenType::Equal
+            | TokenType::NotEqual
+            | TokenType::LessThan
+            | TokenType::LessEqual
+            | TokenType::GreaterThan
+            | TokenType::GreaterEqual
+            | TokenType::In => {
+                let token = self.current_token.clone().unwrap();
+                self.eat(token.clone().type_);
+                Node::BinOp(Box::new(node), token, Box::new(self.simple_expr()))
+            }
+            _ => node,
+        }
+    }
+
     fn empty(&self) -> Node {
         Node::NoOp
     }
 
     fn variable(&mut self) -> Var {
-        let node = Var::new(self.current_token.clone().unwrap());
+        let mut node = Var::new(self.current_token.clone().unwrap());
         self.eat(TokenType::ID);
+
+        loop {
+            match self.current_token.as_ref().unwrap().type_ {
+                TokenType::LeftBracket => {
+                    self.eat(TokenType::LeftBracket);
+                    // a[i, j] is shorthand for a[i][j]
+                    node.selectors.push(Selector::Index(Box::new(self.expr())));
+                    while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
+                        self.eat(TokenType::Comma);
+                        node.selectors.push(Selector::Index(Box::new(self.expr())));
+                    }
+                    self.eat(TokenType::RightBracket);
+                }
+                TokenType::Dot => {
+                    self.eat(TokenType::Dot);
+                    let field = self.current_token.clone().unwrap();
+                    self.eat(TokenType::ID);
+                    node.selectors.push(Selector::Field(field, Vec::new()));
+                }
+                TokenType::Caret => {
+                    let token = self.current_token.clone().unwrap();
+                    self.eat(TokenType::Caret);
+                    node.selectors.push(Selector::Deref(token));
+                }
+                _ => break,
+            }
+        }
         node
     }
 
-    fn assignment_statement(&mut self) -> Node {
-        let left = self.variable();
+    fn assignment_statement(&mut self, left: Var) -> Node {
         let token = self.current_token.clone().unwrap();
         self.eat(TokenType::Assign);
         let right = self.expr();
         Node::Assign(left, token, Box::new(right))
     }
 
+    fn if_statement(&mut self) -> Node {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::If);
+        let condition = self.expr();
+        self.eat(TokenType::Then);
+        let consequence = self.statement();
+
+        // An ELSE always belongs to the innermost IF that can take it
+        let alternative = if let TokenType::Else = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Else);
+            Some(Box::new(self.statement()))
+        } else {
+            None
+        };
+
+        Node::If(
+            token,
+            Box::new(condition),
+            Box::new(consequence),
+            alternative,
+        )
+    }
+
+    fn while_statement(&mut self) -> Node {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::While);
+        let condition = self.expr();
+        self.eat(TokenType::Do);
+        let body = self.statement();
+
+        Node::While(token, Box::new(condition), Box::new(body))
+    }
+
+    fn repeat_statement(&mut self) -> Node {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::Repeat);
+        let body = self.statement_list();
+        self.eat(TokenType::Until);
+        let condition = self.expr();
+
+        Node::Repeat(token, body, Box::new(condition))
+    }
+
+    fn for_statement(&mut self) -> Node {
+        self.eat(TokenType::For);
+        let control = self.variable();
+        self.eat(TokenType::Assign);
+        let start = self.expr();
+        let direction = self.current_token.clone().unwrap();
+        match direction.type_ {
+            TokenType::Downto => self.eat(TokenType::Downto),
+            _ => self.eat(TokenType::To),
+        }
+        let end = self.expr();
+        self.eat(TokenType::Do);
+        let body = self.statement();
+
+        Node::For(
+            control,
+            Box::new(start),
+            direction,
+            Box::new(end),
+            Box::new(body),
+        )
+    }
+
+    fn case_label(&mut self) -> (Node, Option<Node>) {
+        let low = self.simple_expr();
+        if let TokenType::Range = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Range);
+            (low, Some(self.simple_expr()))
+        } else {
+            (low, None)
+        }
+    }
+
+    fn case_arm(&mut self) -> CaseArm {
+        let mut labels = vec![self.case_label()];
+        while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Comma);
+            labels.push(self.case_label());
+        }
+        self.eat(TokenType::Colon);
+
+        CaseArm::new(labels, self.statement())
+    }
+
+    fn case_statement(&mut self) -> Node {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::Case);
+        let selector = self.expr();
+        self.eat(TokenType::Of);
+
+        let mut arms = Vec::new();
+        loop {
+            if let TokenType::Else | TokenType::Otherwise | TokenType::End =
+                self.current_token.as_ref().unwrap().type_
+            {
+                break;
+            }
+            arms.push(self.case_arm());
+            if let TokenType::Semi = self.current_token.as_ref().unwrap().type_ {
+                self.eat(TokenType::Semi);
+            } else {
+                break;
+            }
+        }
+
+        let otherwise = match self.current_token.as_ref().unwrap().type_ {
+            TokenType::Else => {
+                self.eat(TokenType::Else);
+                Some(self.statement_list())
+            }
+            TokenType::Otherwise => {
+                self.eat(TokenType::Otherwise);
+                Some(self.statement_list())
+            }
+            _ => None,
+        };
+        self.eat(TokenType::End);
+
+        Node::Case(token, Box::new(selector), arms, otherwise)
+    }
+
     fn statement(&mut self) -> Node {
         match self.current_token.as_ref().unwrap().type_ {
             TokenType::Begin => self.compound_statement(),
+            TokenType::If => self.if_statement(),
+            TokenType::While => self.while_statement(),
+            TokenType::Repeat => self.repeat_statement(),
+            TokenType::For => self.for_statement(),
+            TokenType::Case => self.case_statement(),
             TokenType::ID => {
-                if let Some('(') = self.lexer.current_char {
-                    self.proccall_statement()
+                let var = self.variable();
+                if let TokenType::Assign = self.current_token.as_ref().unwrap().type_ {
+                    self.assignment_statement(var)
                 } else {
-                    self.assignment_statement()
+                    self.proccall_statement(var.token)
                 }
             }
             _ => self.empty(),
@@ -168,25 +407,42 @@ impl Parser {
         Block::new(declaration_nodes, compound_statement_node)
     }
 
+    /// The declaration part of a block. Like Turbo Pascal, this allows the
+    /// sections in any order and more than once.
     fn declarations(&mut self) -> Vec<Node> {
         let mut declarations = Vec::new();
-        if let TokenType::Var = self.current_token.as_ref().unwrap().type_ {
-            self.eat(TokenType::Var);
-            while let TokenType::ID = self.current_token.as_ref().unwrap().type_ {
-                declarations.append(&mut self.variable_declaration());
-                self.eat(TokenType::Semi);
+        loop {
+            match self.current_token.as_ref().unwrap().type_ {
+                TokenType::Const => {
+                    self.eat(TokenType::Const);
+                    while let TokenType::ID = self.current_token.as_ref().unwrap().type_ {
+                        declarations.push(self.constant_declaration());
+                        self.eat(TokenType::Semi);
+                    }
+                }
+                TokenType::Type => {
+                    self.eat(TokenType::Type);
+                    while let TokenType::ID = self.current_token.as_ref().unwrap().type_ {
+                        declarations.push(self.type_declaration());
+                        self.eat(TokenType::Semi);
+                    }
+                }
+                TokenType::Var => {
+                    self.eat(TokenType::Var);
+                    while let TokenType::ID = self.current_token.as_ref().unwrap().type_ {
+                        declarations.append(&mut self.variable_declaration());
+                        self.eat(TokenType::Semi);
+                    }
+                }
+                TokenType::Procedure => declarations.push(self.procedure_declaration()),
+                TokenType::Function => declarations.push(self.function_declaration()),
+                _ => break,
             }
         }
-        while let TokenType::Procedure = self.current_token.as_ref().unwrap().type_ {
-            declarations.push(self.procedure_declaration())
-        }
         declarations
     }
 
-    fn procedure_declaration(&mut self) -> Node {
-        self.eat(TokenType::Procedure);
-        let proc_name = self.current_token.as_ref().unwrap().value.clone();
-        self.eat(TokenType::ID);
+    fn formal_parameter_part(&mut self) -> Vec<Param> {
         let mut params = Vec::new();
 
         if let TokenType::LeftParen = self.current_token.as_ref().unwrap().type_ {
@@ -197,10 +453,77 @@ impl Parser {
             self.eat(TokenType::RightParen);
         }
 
+        params
+    }
+
+    fn procedure_declaration(&mut self) -> Node {
+        self.eat(TokenType::Procedure);
+        let proc_name = self.current_token.clone().unwrap();
+        self.eat(TokenType::ID);
+        let params = self.formal_parameter_part();
+
         self.eat(TokenType::Semi);
-        let proc_decl = Node::ProcedureDecl(proc_name.to_string(), Box::new(self.block()), params);
+        let block = self.routine_block();
+        Node::ProcedureDecl(ProcedureDecl::new(proc_name, params, None, block))
+    }
+
+    fn function_declaration(&mut self) -> Node {
+        self.eat(TokenType::Function);
+        let func_name = self.current_token.clone().unwrap();
+        self.eat(TokenType::ID);
+        let params = self.formal_parameter_part();
+
+        // Only the definition of a FORWARD function may leave it out
+        let return_type = match self.current_token.as_ref().unwrap().type_ {
+            TokenType::Colon => {
+                self.eat(TokenType::Colon);
+                Some(self.type_spec())
+            }
+            _ => None,
+        };
         self.eat(TokenType::Semi);
-        proc_decl
+        let block = self.routine_block();
+        let mut func_decl = ProcedureDecl::new(func_name, params, return_type, block);
+        func_decl.function = true;
+        Node::ProcedureDecl(func_decl)
+    }
+
+    /// The block of a procedure or function and the semicolon after it, or
+    /// the FORWARD directive saying the block comes later
+    fn routine_block(&mut self) -> Option<Block> {
+        let token = self.current_token.clone().unwrap();
+        let block = match token.type_ {
+            // FORWARD isn't reserved, but no block starts with an identifier
+            TokenType::ID if token.value.expect_string().eq_ignore_ascii_case("forward") => {
+                self.eat(TokenType::ID);
+                None
+            }
+            _ => Some(self.block()),
+        };
+        self.eat(TokenType::Semi);
+        block
+    }
+
+    /// `Max = 100` or the typed constant `Limit: INTEGER = 10`
+    fn constant_declaration(&mut self) -> Node {
+        let name = self.current_token.clone().unwrap();
+        self.eat(TokenType::ID);
+        let type_node = match self.current_token.as_ref().unwrap().type_ {
+            TokenType::Colon => {
+                self.eat(TokenType::Colon);
+                Some(self.type_spec())
+            }
+            _ => None,
+        };
+        self.eat(TokenType::Equal);
+        Node::ConstDecl(name, type_node, Box::new(self.expr()))
+    }
+
+    fn type_declaration(&mut self) -> Node {
+        let name = self.current_token.clone().unwrap();
+        self.eat(TokenType::ID);
+        self.eat(TokenType::Equal);
+        Node::TypeDecl(name, self.type_spec())
     }
 
     fn variable_declaration(&mut self) -> Vec<Node> {
@@ -216,26 +539,236 @@ impl Parser {
         self.eat(TokenType::Colon);
 
         let type_node = self.type_spec();
+        // Only a single variable can be given an initial value
+        let initial_value = match self.current_token.clone().unwrap() {
+            token if token.type_ == TokenType::Equal => {
+                if var_nodes.len() > 1 {
+                    self.error(ErrorCode::UnexpectedToken, token);
+                }
+                self.eat(TokenType::Equal);
+                Some(Box::new(self.expr()))
+            }
+            _ => None,
+        };
         let mut var_declarations = Vec::new();
         for node in var_nodes {
-            var_declarations.push(Node::VarDecl(node, type_node.clone()));
+            var_declarations.push(Node::VarDecl(
+                node,
+                type_node.clone(),
+                initial_value.clone(),
+            ));
         }
         var_declarations
     }
 
-    fn type_spec(&mut self) -> Type {
+    fn ordinal_type(&mut self) -> Type {
         let token = self.current_token.clone().unwrap();
-        if let TokenType::Integer = token.type_ {
-            self.eat(TokenType::Integer);
-        } else {
-            self.eat(TokenType::Real);
+        match token.type_ {
+            TokenType::Integer | TokenType::Boolean | TokenType::Char => {
+                self.eat(token.type_.clone());
+                return Type::new(token);
+            }
+            TokenType::LeftParen => return self.enum_type(),
+            _ => {}
+        }
+
+        // Either a subrange like `1..10` or the name of a type
+        let low = self.simple_expr();
+        match (&self.current_token.as_ref().unwrap().type_, low) {
+            (TokenType::Range, low) => {
+                self.eat(TokenType::Range);
+                let high = self.simple_expr();
+                Type::Subrange(token, Box::new(low), Box::new(high))
+            }
+            (_, Node::Var(var)) if var.selectors.is_empty() => Type::new(var.token),
+            _ => {
+                self.error(ErrorCode::UnexpectedToken, token);
+                unreachable!()
+            }
+        }
+    }
+
+    fn enum_type(&mut self) -> Type {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::LeftParen);
+        let mut names = vec![self.current_token.clone().unwrap()];
+        self.eat(TokenType::ID);
+        while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Comma);
+            names.push(self.current_token.clone().unwrap());
+            self.eat(TokenType::ID);
+        }
+        self.eat(TokenType::RightParen);
+        Type::Enum(token, names)
+    }
+
+    fn array_type(&mut self) -> Type {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::Array);
+        if let TokenType::Of = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Of);
+            return Type::DynArray(token, Box::new(self.type_spec()));
+        }
+        self.eat(TokenType::LeftBracket);
+        let mut indexes = vec![self.ordinal_type()];
+        while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Comma);
+            indexes.push(self.ordinal_type());
+        }
+        self.eat(TokenType::RightBracket);
+        self.eat(TokenType::Of);
+        let element = self.type_spec();
+
+        // ARRAY[a, b] OF T is shorthand for ARRAY[a] OF ARRAY[b] OF T
+        indexes.into_iter().rev().fold(element, |element, index| {
+            Type::Array(token.clone(), Box::new(index), Box::new(element))
+        })
+    }
+
+    fn field_list(&mut self) -> FieldList {
+        let mut fields = Vec::new();
+        let mut variant_part = None;
+        loop {
+            match self.current_token.as_ref().unwrap().type_ {
+                TokenType::ID => {
+                    let mut names = vec![self.current_token.clone().unwrap()];
+                    self.eat(TokenType::ID);
+                    while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
+                        self.eat(TokenType::Comma);
+                        names.push(self.current_token.clone().unwrap());
+                        self.eat(TokenType::ID);
+                    }
+                    self.eat(TokenType::Colon);
+                    let type_node = self.type_spec();
+                    for name in names {
+                        fields.push((name, type_node.clone()));
+                    }
+                }
+                // The variant part always comes last
+                TokenType::Case => {
+                    variant_part = Some(self.variant_part());
+                    break;
+                }
+                _ => break,
+            }
+            if let TokenType::Semi = self.current_token.as_ref().unwrap().type_ {
+                self.eat(TokenType::Semi);
+            } else {
+                break;
+            }
         }
 
-        Type::new(token)
+        FieldList::new(fields, variant_part)
+    }
+
+    fn variant_part(&mut self) -> VariantPart {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::Case);
+        let (tag, tag_type) = match self.current_token.as_ref().unwrap().type_ {
+            TokenType::ID => {
+                let name = self.current_token.clone().unwrap();
+                self.eat(TokenType::ID);
+                if let TokenType::Colon = self.current_token.as_ref().unwrap().type_ {
+                    self.eat(TokenType::Colon);
+                    (Some(name), self.type_spec())
+                } else {
+                    (None, Type::new(name))
+                }
+            }
+            _ => (None, self.type_spec()),
+        };
+        self.eat(TokenType::Of);
+
+        let mut variants = Vec::new();
+        loop {
+            if let TokenType::End | TokenType::RightParen =
+                self.current_token.as_ref().unwrap().type_
+            {
+                break;
+            }
+            let mut labels = vec![self.case_label()];
+            while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
+                self.eat(TokenType::Comma);
+                labels.push(self.case_label());
+            }
+            self.eat(TokenType::Colon);
+            self.eat(TokenType::LeftParen);
+            let fields = self.field_list();
+            self.eat(TokenType::RightParen);
+            variants.push(Variant::new(labels, fields));
+
+            if let TokenType::Semi = self.current_token.as_ref().unwrap().type_ {
+                self.eat(TokenType::Semi);
+            } else {
+                break;
+            }
+        }
+
+        VariantPart::new(token, tag, tag_type, variants)
+    }
+
+    fn record_type(&mut self) -> Type {
+        let token = self.current_token.clone().unwrap();
+        self.eat(TokenType::Record);
+        let fields = self.field_list();
+        self.eat(TokenType::End);
+        Type::Record(token, fields)
+    }
+
+    fn type_spec(&mut self) -> Type {
+        let token = self.current_token.clone().unwrap();
+        match token.type_ {
+            TokenType::Array => self.array_type(),
+            TokenType::Record => self.record_type(),
+            TokenType::Set => {
+                self.eat(TokenType::Set);
+                self.eat(TokenType::Of);
+                Type::Set(token, Box::new(self.ordinal_type()))
+            }
+            TokenType::String => {
+                self.eat(TokenType::String);
+                // STRING[n] holds at most n characters
+                if let TokenType::LeftBracket = self.current_token.as_ref().unwrap().type_ {
+                    self.eat(TokenType::LeftBracket);
+                    let length = self.expr();
+                    self.eat(TokenType::RightBracket);
+                    return Type::String(token, Box::new(length));
+                }
+                Type::new(token)
+            }
+            TokenType::Real => {
+                self.eat(TokenType::Real);
+                Type::new(token)
+            }
+            // The type pointed to may not be declared yet, so it can only be
+            // named here
+            TokenType::Caret => {
+                self.eat(TokenType::Caret);
+                let target = self.current_token.clone().unwrap();
+                match target.type_ {
+                    TokenType::ID
+                    | TokenType::Integer
+                    | TokenType::Real
+                    | TokenType::Boolean
+                    | TokenType::Char
+                    | TokenType::String => self.eat(target.type_.clone()),
+                    _ => self.error(ErrorCode::UnexpectedToken, target.clone()),
+                }
+                Type::Pointer(token, target)
+            }
+            TokenType::Procedure | TokenType::Function => {
+                self.eat(token.type_.clone());
+                self.procedural_type(token)
+            }
+            // Type names and subranges
+            _ => self.ordinal_type(),
+        }
     }
 
     fn formal_parameter_list(&mut self) -> Vec<Param> {
-        if let TokenType::ID = self.current_token.as_ref().unwrap().type_ {
+        if let TokenType::ID | TokenType::Var | TokenType::Procedure | TokenType::Function =
+            self.current_token.as_ref().unwrap().type_
+        {
             let mut param_nodes = self.formal_parameters();
 
             while let TokenType::Semi = self.current_token.as_ref().unwrap().type_ {
@@ -249,6 +782,17 @@ impl Parser {
     }
 
     fn formal_parameters(&mut self) -> Vec<Param> {
+        if let TokenType::Procedure | TokenType::Function =
+            self.current_token.as_ref().unwrap().type_
+        {
+            return vec![self.procedural_parameter()];
+        }
+        let by_reference = if let TokenType::Var = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Var);
+            true
+        } else {
+            false
+        };
         let mut param_nodes = Vec::new();
         let mut param_tokens = vec![self.current_token.clone().unwrap()];
 
@@ -259,33 +803,131 @@ impl Parser {
             self.eat(TokenType::ID);
         }
         self.eat(TokenType::Colon);
-        let type_node = self.type_spec();
+        let type_node = self.parameter_type();
 
         for param_token in param_tokens {
-            param_nodes.push(Param::new(Var::new(param_token), type_node.clone()));
+            param_nodes.push(Param::new(
+                Var::new(param_token),
+                type_node.clone(),
+                by_reference,
+            ));
         }
 
         param_nodes
     }
 
-    fn proccall_statement(&mut self) -> Node {
+    /// `PROCEDURE p(x: INTEGER)` or `FUNCTION f(x: REAL): REAL`, a parameter
+    /// taking a routine
+    fn procedural_parameter(&mut self) -> Param {
         let token = self.current_token.clone().unwrap();
+        self.eat(token.type_.clone());
+        let name = Var::new(self.current_token.clone().unwrap());
+        self.eat(TokenType::ID);
+        let type_node = self.procedural_type(token);
+        Param::new(name, type_node, false)
+    }
+
+    /// The parameters and result type following PROCEDURE or FUNCTION in a
+    /// procedural type
+    fn procedural_type(&mut self, token: Token) -> Type {
+        let params = self.formal_parameter_part();
+        let return_type = match token.type_ {
+            TokenType::Function => {
+                self.eat(TokenType::Colon);
+                Some(Box::new(self.type_spec()))
+            }
+            _ => None,
+        };
+        Type::Procedure(token, params, return_type)
+    }
+
+    /// The type of a formal parameter. Arrays written out here are
+    /// conformant arrays like `ARRAY[lo..hi: INTEGER] OF REAL` or open arrays
+    /// like `ARRAY OF REAL`, whose bounds come from the argument.
+    fn parameter_type(&mut self) -> Type {
+        let token = self.current_token.clone().unwrap();
+        if token.type_ != TokenType::Array {
+            return self.type_spec();
+        }
+        self.eat(TokenType::Array);
+        if let TokenType::Of = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Of);
+            return Type::OpenArray(token, Box::new(self.type_spec()));
+        }
+
+        self.eat(TokenType::LeftBracket);
+        let mut indexes = vec![self.index_type_specification()];
+        while let TokenType::Semi = self.current_token.as_ref().unwrap().type_ {
+            self.eat(TokenType::Semi);
+            indexes.push(self.index_type_specification());
+        }
+        self.eat(TokenType::RightBracket);
+        self.eat(TokenType::Of);
+        let element = self.parameter_type();
+
+        // Like ARRAY[a, b], ARRAY[l1..h1: T1; l2..h2: T2] nests the arrays
+        indexes
+            .into_iter()
+            .rev()
+            .fold(element, |element, (low, high, index)| {
+                Type::Conformant(token.clone(), low, high, Box::new(index), Box::new(element))
+            })
+    }
 
+    /// `lo..hi: T`, naming the bounds of a conformant array
+    fn index_type_specification(&mut self) -> (Token, Token, Type) {
+        let low = self.current_token.clone().unwrap();
         self.eat(TokenType::ID);
+        self.eat(TokenType::Range);
+        let high = self.current_token.clone().unwrap();
+        self.eat(TokenType::ID);
+        self.eat(TokenType::Colon);
+        (low, high, self.ordinal_type())
+    }
+
+    /// An argument, which may carry a field width and precision for Write,
+    /// WriteLn and Str
+    fn actual_parameter(&mut self) -> Node {
+        let node = self.expr();
+        if let TokenType::Colon = self.current_token.as_ref().unwrap().type_ {
+            let token = self.current_token.clone().unwrap();
+            self.eat(TokenType::Colon);
+            let width = self.expr();
+            let precision = match self.current_token.as_ref().unwrap().type_ {
+                TokenType::Colon => {
+                    self.eat(TokenType::Colon);
+                    Some(Box::new(self.expr()))
+                }
+                _ => None,
+            };
+            return Node::Formatted(token, Box::new(node), Box::new(width), precision);
+        }
+        node
+    }
+
+    fn actual_parameters(&mut self) -> Vec<Node> {
         self.eat(TokenType::LeftParen);
         let mut actual_params = Vec::new();
 
-        if let TokenType::LeftParen = self.current_token.as_ref().unwrap().type_ {
+        if let TokenType::RightParen = self.current_token.as_ref().unwrap().type_ {
         } else {
-            actual_params.push(self.expr());
+            actual_params.push(self.actual_parameter());
         }
 
         while let TokenType::Comma = self.current_token.as_ref().unwrap().type_ {
             self.eat(TokenType::Comma);
-            actual_params.push(self.expr());
+            actual_params.push(self.actual_parameter());
         }
 
         self.eat(TokenType::RightParen);
+        actual_params
+    }
+
+    fn proccall_statement(&mut self, token: Token) -> Node {
+        let actual_params = match self.current_token.as_ref().unwrap().type_ {
+            TokenType::LeftParen => self.actual_parameters(),
+            _ => Vec::new(),
+        };
 
         Node::ProcedureCall(ProcedureCall::new(
             token.value.expect_string(),
@@ -1,130 +1,965 @@
 use crate::ast::*;
+use crate::builtins::{self, Dialect, Formal};
 use crate::error::{ErrorCode, SemanticError};
-use crate::interpreter::NodeVisitor;
-use crate::symbols::{ProcedureSymbol, Symbol, SymbolTable, VarSymbol};
-use crate::tokens::Token;
-use crate::tokens::Value;
+use crate::interpreter::{coerce, Interpreter, NodeVisitor};
+use crate::symbols::{
+    ConstSymbol, FieldSymbol, Guard, PointerTarget, ProcedureSymbol, Symbol, SymbolTable,
+    TypeSymbol, VarSymbol,
+};
+use crate::tokens::{Token, TokenType, Value};
+use std::rc::Rc;
 
 pub struct SemanticAnalyzer {
     current_scope: SymbolTable,
+    control_vars: Vec<String>,
+    /// Pointer types whose target hasn't been declared yet
+    forward_pointers: Vec<(Token, PointerTarget)>,
+    /// Routines declared FORWARD and not yet defined, with the level of the
+    /// scope declaring them
+    forward_routines: Vec<(usize, ProcedureDecl)>,
+    dialect: Dialect,
 }
 
 impl SemanticAnalyzer {
-    pub fn new() -> Self {
+    pub fn new(dialect: Dialect) -> Self {
         SemanticAnalyzer {
             current_scope: SymbolTable::new(String::from("global"), 1, None),
+            control_vars: Vec::new(),
+            forward_pointers: Vec::new(),
+            forward_routines: Vec::new(),
+            dialect,
         }
     }
 
     fn error(&self, error_code: ErrorCode, token: Token) {
         SemanticError::new(
-            format!("{} -> {}", error_code.to_string(), token),
+            format!("{} -> {}", error_code, token),
             // error_code,
             // token,
         )
         .throw();
     }
+
+    fn check_assignable(&self, target: &Symbol, source: &Option<Symbol>, token: &Token) {
+        // A nested routine can't outlive the block it's declared in, so only
+        // procedural parameters may hold one, as FPC has it
+        let nested = |type_: &Symbol| type_.signature().is_some_and(|routine| routine.nested);
+        if let Some(source) = source {
+            if target.is_procedural_compatible(source) && nested(source) && !nested(target) {
+                self.error(ErrorCode::NestedRoutineValue, token.clone());
+            }
+        }
+        let compatible = match source {
+            Some(source) => {
+                target.base() == source.base()
+                    || (target.is_builtin("REAL") && source.is_builtin("INTEGER"))
+                    || (target.is_string() && source.is_text())
+                    || target.is_set_compatible(source)
+                    || target.is_pointer_compatible(source)
+                    || target.is_procedural_compatible(source)
+            }
+            None => false,
+        };
+        if !compatible {
+            self.error(ErrorCode::TypeMismatch, token.clone());
+        }
+    }
+
+    /// Evaluates an expression that has already been visited and must be
+    /// known at compile time
+    fn constant(&self, node: &Node, token: &Token) -> Value {
+        if !is_constant(node) {
+            self.error(ErrorCode::ConstantExpected, token.clone());
+        }
+        Interpreter::evaluate(node)
+    }
+
+    /// Visits an expression. A function name on its own is a call without
+    /// arguments, so such variables are turned into calls first, and
+    /// constants are replaced by their values.
+    fn visit_expr(&mut self, node: &mut Node) -> Option<Symbol> {
+        if let Node::Var(var) = node {
+            match (
+                self.current_scope.lookup(var.value.expect_string(), false),
+                var.selectors.is_empty(),
+            ) {
+                (Some(Symbol::Procedure(_) | Symbol::BuiltinProcedure(_)), true) => {
+                    *node = Node::ProcedureCall(ProcedureCall::new(
+                        var.value.expect_string(),
+                        Vec::new(),
+                        var.token.clone(),
+                    ));
+                }
+                // So is a variable holding such a function
+                (Some(Symbol::Var(var_symbol)), true)
+                    if matches!(
+                        var_symbol.type_.definition(),
+                        Symbol::Procedural(function)
+                            if function.return_type.is_some() && function.formal_params.is_empty()
+                    ) =>
+                {
+                    *node = Node::ProcedureCall(ProcedureCall::new(
+                        var.value.expect_string(),
+                        Vec::new(),
+                        var.token.clone(),
+                    ));
+                }
+                // The constant keeps its type, which may be more than its
+                // value tells, like the elements of a set
+                (Some(Symbol::Const(constant)), true) => {
+                    let type_ = constant.type_.clone();
+                    *node = Node::Num(constant.value.clone());
+                    return Some(type_);
+                }
+                _ => {}
+            }
+        }
+        self.visit(node)
+    }
+
+    /// Finds the FORWARD declaration that the routine `decl` defines, which
+    /// is then no longer waiting for its block. Any other routine or
+    /// identifier already declared in this scope makes `decl` a duplicate.
+    fn forward_declaration(
+        &mut self,
+        decl: &ProcedureDecl,
+    ) -> Option<(ProcedureDecl, ProcedureSymbol)> {
+        let symbol = self.current_scope.lookup(decl.name.clone(), true)?.clone();
+        let level = self.current_scope.scope_level;
+        let index = self
+            .forward_routines
+            .iter()
+            .position(|(l, forward)| *l == level && forward.name.eq_ignore_ascii_case(&decl.name));
+        match (symbol, index) {
+            (Symbol::Procedure(forward_symbol), Some(index)) if decl.block.is_some() => {
+                let (_, forward_decl) = self.forward_routines.remove(index);
+                Some((forward_decl, forward_symbol))
+            }
+            _ => {
+                self.error(ErrorCode::DuplicateID, decl.token.clone());
+                unreachable!()
+            }
+        }
+    }
+
+    /// Whether the block of the function `name` encloses the one being
+    /// analyzed, so that the name stands for its result. This is the case
+    /// when the scope declaring `name` is just outside a scope of that name.
+    fn in_function_body(&self, name: &str) -> bool {
+        let mut scope = &self.current_scope;
+        while let Some(enclosing) = &scope.enclosing_scope {
+            if scope.lookup(name.to_string(), true).is_some() {
+                return false;
+            }
+            if enclosing.lookup(name.to_string(), true).is_some() {
+                return scope.scope_name.eq_ignore_ascii_case(name);
+            }
+            scope = enclosing;
+        }
+        false
+    }
+
+    /// Visits an expression whose value is stored into something of type
+    /// `target`. Where a procedural type is wanted, the name of a routine
+    /// stands for the routine itself rather than for a call to it.
+    fn visit_value(&mut self, target: &Symbol, node: &mut Node) -> Option<Symbol> {
+        if let (Symbol::Procedural(_), Node::Var(var)) = (target.definition(), &mut *node) {
+            if var.selectors.is_empty() {
+                match self.current_scope.lookup(var.value.expect_string(), false) {
+                    Some(Symbol::Procedure(routine)) => {
+                        var.type_symbol = Some(Symbol::Procedure(routine.clone()));
+                        return var.type_symbol.clone();
+                    }
+                    Some(Symbol::Var(_)) => return self.visit_var(var),
+                    _ => {}
+                }
+            }
+        }
+        self.visit_expr(node)
+    }
+
+    /// Checks that an argument passed to a VAR parameter is a variable that
+    /// may be changed, and gives its type
+    fn variable_type(&mut self, node: &mut Node, token: &Token) -> Symbol {
+        match node {
+            Node::Var(var) => {
+                if let Some(Symbol::Var(var_symbol)) =
+                    self.current_scope.lookup(var.value.expect_string(), false)
+                {
+                    if var_symbol.read_only {
+                        self.error(ErrorCode::VariableExpected, var.token.clone());
+                    }
+                    if self
+                        .control_vars
+                        .contains(&var.value.expect_string().to_lowercase())
+                    {
+                        self.error(ErrorCode::ControlVariableAssigned, var.token.clone());
+                    }
+                    self.visit_var(var).unwrap()
+                } else {
+                    self.error(ErrorCode::VariableExpected, token.clone());
+                    unreachable!()
+                }
+            }
+            _ => {
+                self.error(ErrorCode::VariableExpected, token.clone());
+                unreachable!()
+            }
+        }
+    }
+
+    /// Checks an actual parameter passed to a VAR parameter, which has to be
+    /// a variable of exactly the formal parameter's type
+    fn check_reference(&mut self, formal_type: &Symbol, node: &mut Node, token: &Token) {
+        if self.variable_type(node, token) != *formal_type {
+            self.error(ErrorCode::TypeMismatch, token.clone());
+        }
+    }
+
+    /// Gives the type an argument of Low, High or SizeOf stands for, which is
+    /// either named directly or the type of a variable
+    fn type_argument(&mut self, node: &mut Node, token: &Token) -> Option<Symbol> {
+        match node {
+            Node::Var(var) => {
+                let symbol = self.current_scope.lookup(var.value.expect_string(), false);
+                match symbol.and_then(type_identifier) {
+                    Some(type_) if var.selectors.is_empty() => {
+                        var.type_symbol = Some(type_);
+                        var.type_symbol.clone()
+                    }
+                    _ => self.visit_var(var),
+                }
+            }
+            _ => {
+                self.error(ErrorCode::VariableExpected, token.clone());
+                unreachable!()
+            }
+        }
+    }
+
+    fn visit_builtin_call(&mut self, procedure_call: &mut ProcedureCall) -> Option<Symbol> {
+        let name = procedure_call.proc_name.to_lowercase();
+        let token = procedure_call.token.clone();
+        let formals = builtins::formals(&name, procedure_call.actual_params.len());
+        if formals.len() != procedure_call.actual_params.len() {
+            self.error(ErrorCode::WrongParamsNum, token.clone());
+        }
+
+        let mut actual_types = Vec::new();
+        for (formal, param_node) in formals.iter().zip(procedure_call.actual_params.iter_mut()) {
+            if !builtins::takes_formats(&name) {
+                self.check_unformatted(param_node);
+            }
+            let (actual_type, check) = match formal {
+                Formal::Value(check) => (self.visit_expr(param_node), check),
+                Formal::Var(check) => (Some(self.variable_type(param_node, &token)), check),
+                Formal::Type(check) => (self.type_argument(param_node, &token), check),
+            };
+            match actual_type {
+                Some(actual_type) if check(&actual_type) => actual_types.push(actual_type),
+                _ => self.error(ErrorCode::TypeMismatch, token.clone()),
+            }
+        }
+        builtins::return_type(&name, &actual_types)
+    }
+
+    /// Evaluates a CASE or variant label, giving the range of ordinals it covers
+    fn case_label(
+        &mut self,
+        label: &mut (Node, Option<Node>),
+        type_: &Symbol,
+        token: &Token,
+    ) -> (i32, i32) {
+        let (low, high) = label;
+        let low_type = self.visit_expr(low);
+        self.check_assignable(type_, &low_type, token);
+        let low = self.constant(low, token).expect_ordinal();
+        let high = match high {
+            Some(high) => {
+                let high_type = self.visit_expr(high);
+                self.check_assignable(type_, &high_type, token);
+                self.constant(high, token).expect_ordinal()
+            }
+            None => low,
+        };
+        (low, high)
+    }
+
+    /// Collects the fields of a record, including those of its variants,
+    /// which are guarded by the tag values selecting them
+    fn record_fields(
+        &mut self,
+        field_list: &mut FieldList,
+        guards: &[Guard],
+        fields: &mut Vec<FieldSymbol>,
+    ) {
+        let mut add_field = |analyzer: &Self, name: &Token, type_: Symbol| {
+            let name_string = name.value.expect_string();
+            if fields
+                .iter()
+                .any(|field| field.name.eq_ignore_ascii_case(&name_string))
+            {
+                analyzer.error(ErrorCode::DuplicateID, name.clone());
+            }
+            fields.push(FieldSymbol::new(name_string, type_, guards.to_vec()));
+        };
+
+        for (name, type_node) in &mut field_list.fields {
+            let type_ = self.visit_type(type_node).unwrap();
+            add_field(self, name, type_);
+        }
+
+        if let Some(variant_part) = &mut field_list.variant_part {
+            let token = variant_part.token.clone();
+            let tag_type = self.visit_type(&mut variant_part.tag_type).unwrap();
+            if !tag_type.is_ordinal() {
+                self.error(ErrorCode::TypeMismatch, token.clone());
+            }
+            if let Some(tag) = &variant_part.tag {
+                add_field(self, tag, tag_type.clone());
+            }
+
+            for variant in &mut variant_part.variants {
+                let labels = variant
+                    .labels
+                    .iter_mut()
+                    .map(|label| self.case_label(label, &tag_type, &token))
+                    .collect();
+                // Without a tag field there is nothing to check against
+                let mut variant_guards = guards.to_vec();
+                if let Some(tag) = &variant_part.tag {
+                    variant_guards.push(Guard::new(tag.value.expect_string(), labels));
+                }
+                self.record_fields(&mut variant.fields, &variant_guards, fields);
+            }
+        }
+    }
+
+    /// Resolves the pointer types declared so far, whose targets have to be
+    /// declared by now. This happens after each TYPE section, after every
+    /// other declaration and after the heading of a routine.
+    fn resolve_pointers(&mut self) {
+        for (token, target) in std::mem::take(&mut self.forward_pointers) {
+            match self
+                .current_scope
+                .lookup(target.name.clone(), false)
+                .and_then(type_identifier)
+            {
+                Some(type_) => target.resolve(type_),
+                None => self.error(ErrorCode::IDNotFound, token),
+            }
+        }
+    }
+
+    fn check_unformatted(&self, node: &Node) {
+        if let Node::Formatted(token, ..) = node {
+            self.error(ErrorCode::UnexpectedFormat, token.clone());
+        }
+    }
+
+    fn check_boolean(&self, type_: &Option<Symbol>, token: &Token) {
+        if !type_.as_ref().is_some_and(|t| t.is_builtin("BOOLEAN")) {
+            self.error(ErrorCode::TypeMismatch, token.clone());
+        }
+    }
+}
+
+/// The type a type identifier stands for, or None for other identifiers
+fn type_identifier(symbol: &Symbol) -> Option<Symbol> {
+    match symbol {
+        Symbol::Builtin(_) => Some(symbol.clone()),
+        Symbol::Type(type_symbol) => Some(type_symbol.type_.clone()),
+        _ => None,
+    }
+}
+
+/// Whether an expression can be evaluated at compile time. The analyzer has
+/// already replaced the constants in it by their values, and calls to
+/// builtin functions are constant when their arguments are. Low, High and
+/// SizeOf only look at the type of theirs, unless the bounds belong to a
+/// dynamic array or an array parameter.
+fn is_constant(node: &Node) -> bool {
+    match node {
+        Node::Num(_) => true,
+        Node::UnaryOp(_, expr) => is_constant(expr),
+        Node::BinOp(left, _, right) => is_constant(left) && is_constant(right),
+        Node::Set(_, elements) => elements
+            .iter()
+            .all(|(low, high)| is_constant(low) && high.as_ref().is_none_or(is_constant)),
+        Node::ProcedureCall(call) if call.proc_symbol.is_none() => {
+            match call.proc_name.to_lowercase().as_str() {
+                "low" | "high" => !matches!(
+                    &call.actual_params[0],
+                    Node::Var(var) if var.type_symbol.as_ref().is_some_and(Symbol::has_dynamic_bounds)
+                ),
+                "sizeof" => true,
+                _ => call.actual_params.iter().all(is_constant),
+            }
+        }
+        _ => false,
+    }
 }
 
 impl NodeVisitor for SemanticAnalyzer {
-    fn visit_num(&mut self, _: &mut Node) -> Value {
-        Value::None
+    type Output = Option<Symbol>;
+
+    fn visit_num(&mut self, num: &mut Node) -> Option<Symbol> {
+        match num {
+            Node::Num(Value::Integer(_)) => Some(Symbol::builtin("INTEGER")),
+            Node::Num(Value::Float(_)) => Some(Symbol::builtin("REAL")),
+            Node::Num(Value::Boolean(_)) => Some(Symbol::builtin("BOOLEAN")),
+            Node::Num(Value::String(_)) => Some(Symbol::builtin("STRING")),
+            Node::Num(Value::Char(_)) => Some(Symbol::builtin("CHAR")),
+            Node::Num(Value::Enum(_, names)) => Some(Symbol::Enum(names.clone())),
+            Node::Num(Value::Pointer(_)) => Some(Symbol::Pointer(None)),
+            _ => None,
+        }
     }
 
-    fn visit_bin_op(&mut self, op: &mut Node) -> Value {
-        if let Node::BinOp(left, _, right) = op {
-            self.visit(left);
-            self.visit(right);
+    fn visit_bin_op(&mut self, op: &mut Node) -> Option<Symbol> {
+        if let Node::BinOp(left, op, right) = op {
+            let left = self.visit_expr(left);
+            let right = self.visit_expr(right);
+            let (left, right) = match (left, right) {
+                (Some(left), Some(right)) => (left, right),
+                _ => {
+                    self.error(ErrorCode::TypeMismatch, op.clone());
+                    unreachable!()
+                }
+            };
+
+            let integers = left.is_builtin("INTEGER") && right.is_builtin("INTEGER");
+            let booleans = left.is_builtin("BOOLEAN") && right.is_builtin("BOOLEAN");
+            let turbo = self.dialect == Dialect::Turbo;
+            let result = match op.type_ {
+                TokenType::Plus | TokenType::Minus | TokenType::Mul
+                    if left.is_set_compatible(&right) =>
+                {
+                    // The type of `[]` says nothing about the elements
+                    match left.definition() {
+                        Symbol::Set(Some(_)) => Some(left),
+                        _ => Some(right),
+                    }
+                }
+                TokenType::Equal
+                | TokenType::NotEqual
+                | TokenType::LessEqual
+                | TokenType::GreaterEqual
+                    if left.is_set_compatible(&right) =>
+                {
+                    Some(Symbol::builtin("BOOLEAN"))
+                }
+                TokenType::Equal | TokenType::NotEqual if left.is_pointer_compatible(&right) => {
+                    Some(Symbol::builtin("BOOLEAN"))
+                }
+                TokenType::In if left.is_ordinal() => match right.definition() {
+                    Symbol::Set(Some(element)) if element.base() == left.base() => {
+                        Some(Symbol::builtin("BOOLEAN"))
+                    }
+                    Symbol::Set(None) => Some(Symbol::builtin("BOOLEAN")),
+                    _ => None,
+                },
+                // Characters joined together make a string too
+                TokenType::Plus if left.is_text() && right.is_text() => {
+                    Some(Symbol::builtin("STRING"))
+                }
+                TokenType::Plus | TokenType::Minus | TokenType::Mul
+                    if left.is_numeric() && right.is_numeric() =>
+                {
+                    if left.is_builtin("REAL") || right.is_builtin("REAL") {
+                        Some(Symbol::builtin("REAL"))
+                    } else {
+                        Some(Symbol::builtin("INTEGER"))
+                    }
+                }
+                TokenType::FloatDiv if left.is_numeric() && right.is_numeric() => {
+                    Some(Symbol::builtin("REAL"))
+                }
+                TokenType::IntegerDiv | TokenType::Mod if integers => {
+                    Some(Symbol::builtin("INTEGER"))
+                }
+                TokenType::And | TokenType::Or if booleans => Some(Symbol::builtin("BOOLEAN")),
+                // Turbo Pascal works on the bits of integers with these
+                TokenType::And
+                | TokenType::Or
+                | TokenType::Xor
+                | TokenType::Shl
+                | TokenType::Shr
+                    if turbo && integers =>
+                {
+                    Some(Symbol::builtin("INTEGER"))
+                }
+                TokenType::Xor if turbo && booleans => Some(Symbol::builtin("BOOLEAN")),
+                TokenType::Equal
+                | TokenType::NotEqual
+                | TokenType::LessThan
+                | TokenType::LessEqual
+                | TokenType::GreaterThan
+                | TokenType::GreaterEqual
+                    if (left.is_ordinal() && left.base() == right.base())
+                        || (left.is_numeric() && right.is_numeric())
+                        || (left.is_text() && right.is_text()) =>
+                {
+                    Some(Symbol::builtin("BOOLEAN"))
+                }
+                _ => None,
+            };
+            if result.is_none() {
+                self.error(ErrorCode::TypeMismatch, op.clone());
+            }
+            result
+        } else {
+            unreachable!()
         }
-        Value::None
     }
 
-    fn visit_unary_op(&mut self, _: &mut Node) -> Value {
-        Value::None
+    fn visit_unary_op(&mut self, unary_op: &mut Node) -> Option<Symbol> {
+        if let Node::UnaryOp(op, expr) = unary_op {
+            let type_ = self.visit_expr(expr);
+            let valid = match op.type_ {
+                TokenType::Not => type_.as_ref().is_some_and(|t| {
+                    t.is_builtin("BOOLEAN")
+                        || (self.dialect == Dialect::Turbo && t.is_builtin("INTEGER"))
+                }),
+                _ => type_.as_ref().is_some_and(|t| t.is_numeric()),
+            };
+            if !valid {
+                self.error(ErrorCode::TypeMismatch, op.clone());
+            }
+            type_
+        } else {
+            unreachable!()
+        }
     }
 
-    fn visit_compound(&mut self, compound: &mut Compound) -> Value {
+    fn visit_compound(&mut self, compound: &mut Compound) -> Option<Symbol> {
         for child in &mut compound.children {
             self.visit(child);
         }
 
-        Value::None
+        None
     }
 
-    fn visit_assign(&mut self, assign: &mut Node) -> Value {
-        if let Node::Assign(left, _, right) = assign {
-            self.visit(right);
-            self.visit_var(left);
+    fn visit_assign(&mut self, assign: &mut Node) -> Option<Symbol> {
+        if let Node::Assign(left, token, right) = assign {
+            match self.current_scope.lookup(left.value.expect_string(), false) {
+                Some(Symbol::Const(_)) => {
+                    self.error(ErrorCode::ConstantAssigned, left.token.clone())
+                }
+                Some(Symbol::Var(var_symbol)) if var_symbol.read_only => {
+                    self.error(ErrorCode::VariableExpected, left.token.clone())
+                }
+                _ => {}
+            }
+            if self
+                .control_vars
+                .contains(&left.value.expect_string().to_lowercase())
+            {
+                self.error(ErrorCode::ControlVariableAssigned, left.token.clone());
+            }
+            let name = left.value.expect_string();
+            let target = match self.current_scope.lookup(name.clone(), false) {
+                // Inside a function, its name stands for the result
+                Some(Symbol::Procedure(function))
+                    if self.in_function_body(&name) && left.selectors.is_empty() =>
+                {
+                    let return_type = function.return_type.clone().map(|t| *t);
+                    left.type_symbol = return_type.clone();
+                    return_type
+                }
+                _ => self.visit_var(left),
+            };
+            match target {
+                Some(target) => {
+                    let source = self.visit_value(&target, right);
+                    self.check_assignable(&target, &source, token)
+                }
+                None => {
+                    self.visit_expr(right);
+                    self.error(ErrorCode::TypeMismatch, token.clone())
+                }
+            }
         }
 
-        Value::None
+        None
     }
 
-    fn visit_var(&mut self, var: &mut Var) -> Value {
+    fn visit_var(&mut self, var: &mut Var) -> Option<Symbol> {
         let var_name = var.value.expect_string();
-        self.current_scope
-            .lookup(var_name, false)
-            .unwrap_or_else(|| {
+        let mut type_ = match self.current_scope.lookup(var_name, false) {
+            Some(Symbol::Var(var_symbol)) => Some(var_symbol.type_.clone()),
+            Some(_) => None,
+            None => {
                 self.error(ErrorCode::IDNotFound, var.token.clone());
                 unreachable!()
-            });
+            }
+        };
+
+        for selector in &mut var.selectors {
+            match selector {
+                Selector::Index(index) | Selector::Position(index) => {
+                    let (index_type, element_type) = match type_.as_ref().map(Symbol::definition) {
+                        Some(
+                            Symbol::Array(index_type, element_type)
+                            | Symbol::Conformant(index_type, element_type),
+                        ) => (*index_type.clone(), *element_type.clone()),
+                        Some(Symbol::DynArray(element_type) | Symbol::OpenArray(element_type)) => {
+                            (Symbol::builtin("INTEGER"), *element_type.clone())
+                        }
+                        // Strings are indexed by character position
+                        Some(string) if string.is_string() => {
+                            (Symbol::builtin("INTEGER"), Symbol::builtin("CHAR"))
+                        }
+                        _ => {
+                            self.error(ErrorCode::TypeMismatch, var.token.clone());
+                            unreachable!()
+                        }
+                    };
+                    let actual_type = self.visit_expr(index);
+                    self.check_assignable(&index_type, &actual_type, &var.token);
+                    // Open arrays are indexed from zero, whatever array was
+                    // passed
+                    let open = matches!(
+                        type_.as_ref().map(Symbol::definition),
+                        Some(Symbol::OpenArray(_))
+                    );
+                    if open {
+                        let index = std::mem::replace(index, Box::new(Node::NoOp));
+                        *selector = Selector::Position(index);
+                    }
+                    type_ = Some(element_type);
+                }
+                Selector::Field(name, guards) => {
+                    let field_name = name.value.expect_string();
+                    let field = match type_.as_ref().map(Symbol::definition) {
+                        Some(Symbol::Record(fields)) => fields
+                            .iter()
+                            .find(|field| field.name.eq_ignore_ascii_case(&field_name)),
+                        _ => {
+                            self.error(ErrorCode::TypeMismatch, var.token.clone());
+                            unreachable!()
+                        }
+                    };
+                    match field.cloned() {
+                        Some(field) => {
+                            *guards = field.guards;
+                            type_ = Some(field.type_);
+                        }
+                        None => self.error(ErrorCode::IDNotFound, name.clone()),
+                    }
+                }
+                Selector::Deref(token) => match type_.as_ref().map(Symbol::definition) {
+                    Some(Symbol::Pointer(Some(target))) => type_ = Some(target.type_()),
+                    _ => self.error(ErrorCode::TypeMismatch, token.clone()),
+                },
+            }
+        }
 
-        Value::None
+        var.type_symbol = type_.clone();
+        type_
     }
 
-    fn visit_program(&mut self, program: &mut Node) -> Value {
-        println!("ENTER scope: global");
-        self.current_scope = SymbolTable::new(String::from("global"), 1, None);
+    fn visit_program(&mut self, program: &mut Node) -> Option<Symbol> {
+        let mut builtins = SymbolTable::new(String::from("builtins"), 0, None);
+        if self.dialect == Dialect::Turbo {
+            for name in builtins::TURBO_ROUTINES {
+                builtins.insert(Symbol::BuiltinProcedure(String::from(*name)));
+            }
+        }
+        self.current_scope = SymbolTable::new(String::from("global"), 1, Some(builtins));
         if let Node::Program(_, block) = program {
             self.visit_block(block);
         }
-        self.current_scope = *std::mem::replace(&mut self.current_scope.enclosing_scope, None)
+        self.current_scope = *self
+            .current_scope
+            .enclosing_scope
+            .take()
             .unwrap_or_else(|| Box::new(SymbolTable::new(String::new(), 0, None)));
         // self.print_symbols();
-        println!("LEAVE scope: global");
-        Value::None
+        None
     }
 
-    fn visit_block(&mut self, block: &mut Block) -> Value {
+    fn visit_block(&mut self, block: &mut Block) -> Option<Symbol> {
         for declaration in &mut block.declarations {
+            // Pointer types may refer to types declared later in the same
+            // TYPE section, but no further
+            if !matches!(declaration, Node::TypeDecl(..)) {
+                self.resolve_pointers();
+            }
             self.visit(declaration);
         }
+        self.resolve_pointers();
+        let level = self.current_scope.scope_level;
+        if let Some((_, routine)) = self.forward_routines.iter().find(|(l, _)| *l == level) {
+            self.error(ErrorCode::ForwardNotSolved, routine.token.clone());
+        }
         self.visit(&mut block.compound_statement);
 
-        Value::None
+        None
     }
 
-    fn visit_var_decl(&mut self, var_decl: &mut Node) -> Value {
-        if let Node::VarDecl(var_node, type_node) = var_decl {
+    fn visit_var_decl(&mut self, var_decl: &mut Node) -> Option<Symbol> {
+        if let Node::VarDecl(var_node, type_node, initial_value) = var_decl {
             let var_name = var_node.value.expect_string();
             if self.current_scope.lookup(var_name.clone(), true).is_some() {
                 self.error(ErrorCode::DuplicateID, var_node.token.clone());
             }
 
+            let type_symbol = self.visit_type(type_node).unwrap();
+            if let Some(initial_value) = initial_value {
+                let value_type = self.visit_expr(initial_value);
+                self.check_assignable(&type_symbol, &value_type, &var_node.token);
+                let value = self.constant(initial_value, &var_node.token);
+                **initial_value = Node::Num(value);
+            }
+            var_node.type_symbol = Some(type_symbol.clone());
             self.current_scope
-                .insert(Symbol::Var(Box::new(VarSymbol::new(
-                    var_name,
-                    self.current_scope
-                        .lookup(type_node.value.expect_string().to_uppercase(), false)
-                        .unwrap()
-                        .clone(),
+                .insert(Symbol::Var(Box::new(VarSymbol::new(var_name, type_symbol))));
+        }
+
+        None
+    }
+
+    fn visit_type_decl(&mut self, type_decl: &mut Node) -> Option<Symbol> {
+        if let Node::TypeDecl(name, type_node) = type_decl {
+            let type_name = name.value.expect_string();
+            if self.current_scope.lookup(type_name.clone(), true).is_some() {
+                self.error(ErrorCode::DuplicateID, name.clone());
+            }
+
+            let type_ = self.visit_type(type_node).unwrap();
+            // A new structure is a new type, known by this name. The values
+            // of an enumeration already set it apart from any other, as the
+            // target does for pointers.
+            let type_ = match type_node {
+                Type::Named(_) | Type::Enum(..) | Type::Pointer(..) => type_,
+                _ => Symbol::Named(type_name.clone(), Rc::new(type_)),
+            };
+            self.current_scope
+                .insert(Symbol::Type(Box::new(TypeSymbol::new(type_name, type_))));
+        }
+
+        None
+    }
+
+    fn visit_const_decl(&mut self, const_decl: &mut Node) -> Option<Symbol> {
+        if let Node::ConstDecl(name, type_node, value) = const_decl {
+            let const_name = name.value.expect_string();
+            if self
+                .current_scope
+                .lookup(const_name.clone(), true)
+                .is_some()
+            {
+                self.error(ErrorCode::DuplicateID, name.clone());
+            }
+
+            let value_type = self.visit_expr(value);
+            let type_ = match type_node {
+                Some(type_node) => {
+                    let type_ = self.visit_type(type_node).unwrap();
+                    self.check_assignable(&type_, &value_type, name);
+                    type_
+                }
+                None => match value_type {
+                    Some(type_) => type_,
+                    None => {
+                        self.error(ErrorCode::TypeMismatch, name.clone());
+                        unreachable!()
+                    }
+                },
+            };
+            let value = coerce(self.constant(value, name), &Some(type_.clone()));
+            self.current_scope
+                .insert(Symbol::Const(Box::new(ConstSymbol::new(
+                    const_name, type_, value,
                 ))));
         }
 
-        Value::None
+        None
     }
 
-    fn visit_type(&mut self, _: &mut Type) -> Value {
-        Value::None
+    fn visit_type(&mut self, type_: &mut Type) -> Option<Symbol> {
+        match type_ {
+            Type::Named(token) => match self
+                .current_scope
+                .lookup(token.value.expect_string(), false)
+                .and_then(type_identifier)
+            {
+                Some(type_) => Some(type_),
+                None => {
+                    self.error(ErrorCode::IDNotFound, token.clone());
+                    unreachable!()
+                }
+            },
+            Type::Enum(_, names) => {
+                let type_ = Symbol::Enum(Rc::new(
+                    names
+                        .iter()
+                        .map(|name| name.value.expect_string())
+                        .collect(),
+                ));
+                // The values are constants of the block declaring the type
+                for (ordinal, name) in names.iter().enumerate() {
+                    let const_name = name.value.expect_string();
+                    if self
+                        .current_scope
+                        .lookup(const_name.clone(), true)
+                        .is_some()
+                    {
+                        self.error(ErrorCode::DuplicateID, name.clone());
+                    }
+                    let value = builtins::ordinal_value(&type_, ordinal as i32);
+                    self.current_scope
+                        .insert(Symbol::Const(Box::new(ConstSymbol::new(
+                            const_name,
+                            type_.clone(),
+                            value,
+                        ))));
+                }
+                Some(type_)
+            }
+            Type::Subrange(token, low, high) => {
+                let low_type = self.visit_expr(low);
+                let high_type = self.visit_expr(high);
+                let base = match (low_type, high_type) {
+                    (Some(low), Some(high)) if low.is_ordinal() && low == high => low,
+                    _ => {
+                        self.error(ErrorCode::TypeMismatch, token.clone());
+                        unreachable!()
+                    }
+                };
+                let low = self.constant(low, token).expect_ordinal();
+                let high = self.constant(high, token).expect_ordinal();
+                if low > high {
+                    self.error(ErrorCode::InvalidRange, token.clone());
+                }
+                Some(Symbol::Subrange(Box::new(base), low, high))
+            }
+            Type::Array(_, index_node, element) => {
+                let index = self.visit_type(index_node).unwrap();
+                // The index type has to be finite, so INTEGER itself won't do
+                if index.bounds().is_none() {
+                    self.error(ErrorCode::TypeMismatch, index_node.token().clone());
+                }
+                let element = self.visit_type(element).unwrap();
+                Some(Symbol::Array(Box::new(index), Box::new(element)))
+            }
+            // A Free Pascal extension, like SetLength that sizes them
+            Type::DynArray(token, element) => {
+                if self.dialect != Dialect::Turbo {
+                    self.error(ErrorCode::TurboOnly, token.clone());
+                }
+                let element = self.visit_type(element).unwrap();
+                Some(Symbol::DynArray(Box::new(element)))
+            }
+            // ISO programs take arrays of any length through conformant
+            // arrays, whose bounds are named
+            Type::OpenArray(token, element) => {
+                if self.dialect != Dialect::Turbo {
+                    self.error(ErrorCode::TurboOnly, token.clone());
+                }
+                let element = self.visit_type(element).unwrap();
+                Some(Symbol::OpenArray(Box::new(element)))
+            }
+            Type::Conformant(token, low, high, index_node, element) => {
+                let index = self.visit_type(index_node).unwrap();
+                if !index.is_ordinal() {
+                    self.error(ErrorCode::TypeMismatch, token.clone());
+                }
+                // The bounds are declared along with the parameters. In
+                // `a, b: ARRAY[lo..hi: INTEGER] OF REAL` both parameters
+                // declare them.
+                for bound in [low, high] {
+                    let name = bound.value.expect_string();
+                    match self.current_scope.lookup(name.clone(), true) {
+                        Some(Symbol::Var(var_symbol)) if var_symbol.read_only => {}
+                        Some(_) => self.error(ErrorCode::DuplicateID, bound.clone()),
+                        None => {
+                            let mut var_symbol = VarSymbol::new(name, index.clone());
+                            var_symbol.read_only = true;
+                            self.current_scope.insert(Symbol::Var(Box::new(var_symbol)));
+                        }
+                    }
+                }
+                let element = self.visit_type(element).unwrap();
+                Some(Symbol::Conformant(Box::new(index), Box::new(element)))
+            }
+            Type::Set(token, element) => {
+                let element = self.visit_type(element).unwrap();
+                // Sets hold the ordinals 0 to 255, like in Turbo Pascal
+                match element.bounds() {
+                    Some((low, high)) if low >= 0 && high <= 255 => {
+                        Some(Symbol::Set(Some(Box::new(element))))
+                    }
+                    _ => {
+                        self.error(ErrorCode::TypeMismatch, token.clone());
+                        unreachable!()
+                    }
+                }
+            }
+            // The target is looked up once the declarations around are done,
+            // so that a type declared later in the same TYPE section hides
+            // one from an enclosing block
+            Type::Pointer(_, name) => {
+                let target = PointerTarget::new(name.value.expect_string());
+                self.forward_pointers.push((name.clone(), target.clone()));
+                Some(Symbol::Pointer(Some(target)))
+            }
+            Type::Procedure(_, params, return_type) => {
+                let mut procedure = ProcedureSymbol::new(String::new(), Vec::new());
+                for param in params {
+                    let mut var_symbol = VarSymbol::new(
+                        param.var_node.value.expect_string(),
+                        self.visit_type(&mut param.type_node).unwrap(),
+                    );
+                    var_symbol.by_reference = param.by_reference;
+                    procedure.formal_params.push(var_symbol);
+                }
+                if let Some(return_type) = return_type {
+                    let return_type = self.visit_type(return_type).unwrap();
+                    procedure.return_type = Some(Box::new(return_type));
+                }
+                Some(Symbol::Procedural(Box::new(procedure)))
+            }
+            Type::Record(_, field_list) => {
+                let mut fields = Vec::new();
+                self.record_fields(field_list, &[], &mut fields);
+                Some(Symbol::Record(fields))
+            }
+            Type::String(token, length) => {
+                let length_type = self.visit_expr(length);
+                self.check_assignable(&Symbol::builtin("INTEGER"), &length_type, token);
+                // Turbo Pascal's short strings store their length in a byte
+                match self.constant(length, token).expect_ordinal() {
+                    length @ 1..=255 => Some(Symbol::String(length)),
+                    _ => {
+                        self.error(ErrorCode::InvalidStringLength, token.clone());
+                        unreachable!()
+                    }
+                }
+            }
+        }
     }
 
-    fn visit_procedure_decl(&mut self, procedure_decl: &mut Node) -> Value {
-        if let Node::ProcedureDecl(proc_name, block_node, formal_params) = procedure_decl {
+    fn visit_procedure_decl(&mut self, procedure_decl: &mut Node) -> Option<Symbol> {
+        if let Node::ProcedureDecl(decl) = procedure_decl {
+            let proc_name = decl.name.clone();
             let mut proc_symbol = ProcedureSymbol::new(proc_name.clone(), Vec::new());
-            // self.current_scope
-            //     .insert(Symbol::Procedure(proc_symbol.clone()));
-
-            println!("ENTER scope: {}", proc_name.clone());
+            proc_symbol.nested = self.current_scope.scope_level > 1;
+            let forward = self.forward_declaration(decl);
+            if let Some((forward_decl, _)) = &forward {
+                if forward_decl.function != decl.function {
+                    self.error(ErrorCode::HeadingMismatch, decl.token.clone());
+                }
+                // The definition may leave out the heading given before
+                if decl.params.is_empty() && decl.return_type.is_none() {
+                    decl.params = forward_decl.params.clone();
+                    decl.return_type = forward_decl.return_type.clone();
+                }
+            }
+            if decl.function && decl.return_type.is_none() {
+                self.error(ErrorCode::ResultTypeExpected, decl.token.clone());
+            }
 
             let level = self.current_scope.scope_level + 1;
             let prev_scope = std::mem::replace(
@@ -133,59 +968,1170 @@ impl NodeVisitor for SemanticAnalyzer {
             );
             self.current_scope = SymbolTable::new(proc_name.clone(), level, Some(prev_scope));
 
-            for param in formal_params {
-                let var_symbol = VarSymbol::new(
-                    param.var_node.value.expect_string(),
-                    self.current_scope
-                        .lookup(param.type_node.value.expect_string().to_uppercase(), false)
-                        .unwrap()
-                        .clone(),
-                );
+            for param in &mut decl.params {
+                let mut type_ = self.visit_type(&mut param.type_node).unwrap();
+                // A procedural parameter may take routines nested anywhere
+                // around the call
+                if let Symbol::Procedural(routine) = &mut type_ {
+                    routine.nested = matches!(param.type_node, Type::Procedure(..));
+                }
+                let mut var_symbol = VarSymbol::new(param.var_node.value.expect_string(), type_);
+                var_symbol.by_reference = param.by_reference;
+                param.var_node.type_symbol = Some(var_symbol.type_.clone());
                 self.current_scope
                     .insert(Symbol::Var(Box::new(var_symbol.clone())));
                 proc_symbol.formal_params.push(var_symbol);
             }
-            proc_symbol.block_ast = Some(block_node.clone());
+            if let Some(return_type) = &mut decl.return_type {
+                let return_type = self.visit_type(return_type).unwrap();
+                // In Turbo Pascal `Result` is an alias for the function name
+                // inside the body, unless a parameter already has that name
+                let result = String::from("Result");
+                if self.dialect == Dialect::Turbo
+                    && self.current_scope.lookup(result.clone(), true).is_none()
+                {
+                    self.current_scope
+                        .insert(Symbol::Var(Box::new(VarSymbol::new(
+                            result,
+                            return_type.clone(),
+                        ))));
+                    decl.result_alias = true;
+                }
+                proc_symbol.return_type = Some(Box::new(return_type));
+            }
+            self.resolve_pointers();
+            // A heading written out again has to be the same, down to the
+            // names of the parameters the block uses
+            if let Some((_, forward_symbol)) = &forward {
+                let same_names = proc_symbol
+                    .formal_params
+                    .iter()
+                    .zip(&forward_symbol.formal_params)
+                    .all(|(left, right)| left.name.eq_ignore_ascii_case(&right.name));
+                if !same_names || !proc_symbol.same_signature(forward_symbol) {
+                    self.error(ErrorCode::HeadingMismatch, decl.token.clone());
+                }
+            }
             if let Some(scope) = self.current_scope.enclosing_scope.as_mut() {
                 scope.insert(Symbol::Procedure(proc_symbol))
             };
 
-            self.visit_block(block_node);
+            match &mut decl.block {
+                Some(block) => {
+                    self.visit_block(block);
+                }
+                None => self.forward_routines.push((level - 1, decl.clone())),
+            }
 
             // self.print_symbols();
-            self.current_scope =
-                *std::mem::replace(&mut self.current_scope.enclosing_scope, None).unwrap();
-            println!("LEAVE scope: {}", proc_name.clone());
-
-            // proc_symbol.block_ast = Some(Box::new(procedure_decl.block_node.clone()));
+            self.current_scope = *self.current_scope.enclosing_scope.take().unwrap();
         }
 
-        Value::None
+        None
     }
 
-    fn visit_procedure_call(&mut self, procedure_call: &mut ProcedureCall) -> Value {
-        if let Some(Symbol::Procedure(proc)) = self
+    fn visit_procedure_call(&mut self, procedure_call: &mut ProcedureCall) -> Option<Symbol> {
+        let proc = match self
             .current_scope
-            .lookup(procedure_call.proc_name.clone(), true)
+            .lookup(procedure_call.proc_name.clone(), false)
         {
+            Some(Symbol::Procedure(proc)) => Some(proc.clone()),
+            // Calls through a variable of a procedural type
+            Some(Symbol::Var(var_symbol)) => match var_symbol.type_.definition() {
+                Symbol::Procedural(proc) => Some((**proc).clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(proc) = proc {
             if proc.formal_params.len() != procedure_call.actual_params.len() {
                 self.error(ErrorCode::WrongParamsNum, procedure_call.token.clone());
             }
-            for param_node in &mut procedure_call.actual_params {
-                self.visit(param_node);
-            }
-            procedure_call.proc_symbol = match self
-                .current_scope
-                .lookup(procedure_call.proc_name.clone(), false)
+            for (formal, param_node) in proc
+                .formal_params
+                .iter()
+                .zip(procedure_call.actual_params.iter_mut())
             {
-                Some(Symbol::Procedure(s)) => Some(s.clone()),
-                _ => panic!(),
-            };
+                self.check_unformatted(param_node);
+                let token = &procedure_call.token;
+                // Array parameters take arrays of any bounds
+                if formal.type_.is_open() {
+                    let actual_type = match formal.by_reference {
+                        true => Some(self.variable_type(param_node, token)),
+                        false => self.visit_expr(param_node),
+                    };
+                    if !actual_type.is_some_and(|actual| formal.type_.conforms(&actual)) {
+                        self.error(ErrorCode::TypeMismatch, token.clone());
+                    }
+                } else if formal.by_reference {
+                    self.check_reference(&formal.type_, param_node, token);
+                } else {
+                    let actual_type = self.visit_value(&formal.type_, param_node);
+                    self.check_assignable(&formal.type_, &actual_type, token);
+                }
+            }
+            procedure_call.proc_symbol = Some(proc.clone());
+            proc.return_type.map(|return_type| *return_type)
+        } else if let Some(Symbol::BuiltinProcedure(_)) = self
+            .current_scope
+            .lookup(procedure_call.proc_name.clone(), false)
+        {
+            self.visit_builtin_call(procedure_call)
         } else {
-            println!("{:?}", self.current_scope);
             self.error(ErrorCode::IDNotFound, procedure_call.token.clone());
+            unreachable!()
+        }
+    }
+
+    fn visit_if(&mut self, if_: &mut Node) -> Option<Symbol> {
+        if let Node::If(token, condition, consequence, alternative) = if_ {
+            let condition_type = self.visit_expr(condition);
+            self.check_boolean(&condition_type, token);
+            self.visit(consequence);
+            if let Some(alternative) = alternative {
+                self.visit(alternative);
+            }
+        }
+
+        None
+    }
+
+    fn visit_while(&mut self, while_: &mut Node) -> Option<Symbol> {
+        if let Node::While(token, condition, body) = while_ {
+            let condition_type = self.visit_expr(condition);
+            self.check_boolean(&condition_type, token);
+            self.visit(body);
+        }
+
+        None
+    }
+
+    fn visit_repeat(&mut self, repeat: &mut Node) -> Option<Symbol> {
+        if let Node::Repeat(token, body, condition) = repeat {
+            for statement in body {
+                self.visit(statement);
+            }
+            let condition_type = self.visit_expr(condition);
+            self.check_boolean(&condition_type, token);
+        }
+
+        None
+    }
+
+    fn visit_for(&mut self, for_: &mut Node) -> Option<Symbol> {
+        if let Node::For(control, start, direction, end, body) = for_ {
+            let name = control.value.expect_string();
+            // ISO 7185 requires a local variable of an ordinal type, which a
+            // VAR parameter standing for some other variable isn't
+            let control_type = match self.current_scope.lookup(name.clone(), true) {
+                Some(Symbol::Var(var_symbol))
+                    if var_symbol.type_.is_ordinal()
+                        && !var_symbol.read_only
+                        && !var_symbol.by_reference
+                        && control.selectors.is_empty() =>
+                {
+                    var_symbol.type_.clone()
+                }
+                _ => {
+                    self.error(ErrorCode::InvalidControlVariable, control.token.clone());
+                    unreachable!()
+                }
+            };
+            if self.control_vars.contains(&name.to_lowercase()) {
+                self.error(ErrorCode::ControlVariableAssigned, control.token.clone());
+            }
+
+            let start_type = self.visit_expr(start);
+            self.check_assignable(&control_type, &start_type, &control.token);
+            let end_type = self.visit_expr(end);
+            self.check_assignable(&control_type, &end_type, direction);
+
+            self.control_vars.push(name.to_lowercase());
+            self.visit(body);
+            self.control_vars.pop();
+        }
+
+        None
+    }
+
+    fn visit_case(&mut self, case: &mut Node) -> Option<Symbol> {
+        if let Node::Case(token, selector, arms, otherwise) = case {
+            let selector_type = self.visit_expr(selector);
+            let selector_type = match selector_type {
+                Some(type_) if type_.is_ordinal() => type_,
+                _ => {
+                    self.error(ErrorCode::TypeMismatch, token.clone());
+                    unreachable!()
+                }
+            };
+
+            let mut ranges: Vec<(i32, i32)> = Vec::new();
+            for arm in arms {
+                for label in &mut arm.labels {
+                    let (low, high) = self.case_label(label, &selector_type, token);
+                    if ranges.iter().any(|&(l, h)| low <= h && l <= high) {
+                        self.error(ErrorCode::DuplicateCaseLabel, token.clone());
+                    }
+                    ranges.push((low, high));
+                }
+                self.visit(&mut arm.statement);
+            }
+
+            if let Some(otherwise) = otherwise {
+                for statement in otherwise {
+                    self.visit(statement);
+                }
+            }
         }
 
-        Value::None
+        None
+    }
+
+    fn visit_set(&mut self, set: &mut Node) -> Option<Symbol> {
+        if let Node::Set(token, elements) = set {
+            let mut element_type: Option<Symbol> = None;
+            for (low, high) in elements {
+                for node in std::iter::once(low).chain(high) {
+                    let type_ = self.visit_expr(node);
+                    match (&type_, &element_type) {
+                        (Some(type_), None) if type_.is_ordinal() => {
+                            element_type = Some(type_.base().clone())
+                        }
+                        (Some(type_), Some(element_type)) if type_.base() == element_type => {}
+                        _ => self.error(ErrorCode::TypeMismatch, token.clone()),
+                    }
+                }
+            }
+            Some(Symbol::Set(element_type.map(Box::new)))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn visit_formatted(&mut self, formatted: &mut Node) -> Option<Symbol> {
+        if let Node::Formatted(token, expr, width, precision) = formatted {
+            let type_ = self.visit_expr(expr);
+            let integer = Symbol::builtin("INTEGER");
+            let width_type = self.visit_expr(width);
+            self.check_assignable(&integer, &width_type, token);
+            // Only REAL values can be written with a precision
+            if let Some(precision) = precision {
+                let precision_type = self.visit_expr(precision);
+                self.check_assignable(&integer, &precision_type, token);
+                if !type_.as_ref().is_some_and(|t| t.is_builtin("REAL")) {
+                    self.error(ErrorCode::TypeMismatch, token.clone());
+                }
+            }
+            type_
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Parser};
+
+    fn analyze_as(dialect: Dialect, text: &str) {
+        let lexer = Lexer::new(text.to_string());
+        let mut parser = Parser::new(lexer);
+        let mut tree = parser.parse();
+        SemanticAnalyzer::new(dialect).visit(&mut tree);
+    }
+
+    fn analyze(text: &str) {
+        analyze_as(Dialect::Iso, text);
+    }
+
+    fn analyze_turbo(text: &str) {
+        analyze_as(Dialect::Turbo, text);
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn non_boolean_condition() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : INTEGER;
+
+BEGIN
+    IF x + 1 THEN x := 0
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn non_boolean_loop_condition() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : INTEGER;
+
+BEGIN
+    REPEAT
+        x := x + 1
+    UNTIL x
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Assignment to FOR control variable")]
+    fn assign_to_control_variable() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    i : INTEGER;
+
+BEGIN
+    FOR i := 1 TO 10 DO
+        IF i = 5 THEN i := 10
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid FOR control variable")]
+    fn non_local_control_variable() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    i : INTEGER;
+
+PROCEDURE P(n : INTEGER);
+BEGIN
+    FOR i := 1 TO n DO
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid FOR control variable")]
+    fn var_parameter_control_variable() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    k : INTEGER;
+
+PROCEDURE P(VAR i : INTEGER);
+BEGIN
+    FOR i := 1 TO 3 DO
+        WriteLn(i)
+END;
+
+BEGIN
+    k := 99;
+    P(k)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate case label")]
+    fn overlapping_case_labels() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x, y : INTEGER;
+
+BEGIN
+    CASE x OF
+        1, 2: y := 1;
+        5..9: y := 2;
+        -1, 9..12: y := 3
+    END
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Constant expression expected")]
+    fn variable_case_label() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x, y : INTEGER;
+
+BEGIN
+    CASE x OF
+        y: x := 1
+    END
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Identifier not found")]
+    fn unknown_return_type() {
+        analyze(
+            "
+PROGRAM Bad;
+
+FUNCTION F(x : INTEGER) : Widget;
+BEGIN
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn procedure_in_expression() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : INTEGER;
+
+PROCEDURE P(n : INTEGER);
+BEGIN
+END;
+
+BEGIN
+    x := P(1) + 1
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable expected")]
+    fn expression_for_var_param() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : INTEGER;
+
+PROCEDURE Inc(VAR n : INTEGER);
+BEGIN
+    n := n + 1
+END;
+
+BEGIN
+    Inc(x + 1)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn var_param_type_must_match() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : INTEGER;
+
+PROCEDURE Scale(VAR r : REAL);
+BEGIN
+    r := r * 2
+END;
+
+BEGIN
+    Scale(x)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn boolean_to_integer() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : INTEGER;
+
+BEGIN
+    x := 1 < 2
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn index_non_array() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : INTEGER;
+    a : ARRAY[1..3, 1..3] OF INTEGER;
+
+BEGIN
+    x := a[1, 2, 3]
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn boolean_array_index() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    a : ARRAY[1..3] OF INTEGER;
+
+BEGIN
+    a[TRUE] := 1
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Identifier not found")]
+    fn unknown_field() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    p : RECORD x, y : INTEGER END;
+
+BEGIN
+    p.z := 1
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate id found")]
+    fn duplicate_variant_field() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    p : RECORD
+        x : INTEGER;
+        CASE BOOLEAN OF
+            TRUE: (y : INTEGER);
+            FALSE: (x : REAL)
+    END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn string_plus_integer() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    s : STRING;
+
+BEGIN
+    s := 'count: ' + 1
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable expected")]
+    fn delete_from_literal() {
+        analyze(
+            "
+PROGRAM Bad;
+BEGIN
+    Delete('abc', 1, 1)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn char_plus_integer() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    c : CHAR;
+
+BEGIN
+    c := 'a' + 1
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn compare_char_with_integer() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    c : CHAR;
+
+BEGIN
+    IF c < 65 THEN c := 'A'
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Field width not allowed here")]
+    fn field_width_in_call() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    n : INTEGER;
+
+PROCEDURE P(x : INTEGER);
+BEGIN
+END;
+
+BEGIN
+    P(n:3)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn read_boolean() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    b : BOOLEAN;
+
+BEGIN
+    ReadLn(b)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn round_returns_integer() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    b : BOOLEAN;
+
+BEGIN
+    b := Round(2.5) OR Odd(3)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Identifier not found")]
+    fn pi_is_turbo_only() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : REAL;
+
+BEGIN
+    x := Pi
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable expected")]
+    fn inc_of_expression() {
+        analyze_turbo(
+            "
+PROGRAM Bad;
+VAR
+    i : INTEGER;
+
+BEGIN
+    Inc(i + 1)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn high_of_real() {
+        analyze_turbo(
+            "
+PROGRAM Bad;
+VAR
+    x : REAL;
+
+BEGIN
+    x := High(x)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn bitwise_and_is_turbo_only() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    i : INTEGER;
+
+BEGIN
+    i := i AND 1
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Assignment to constant")]
+    fn assign_to_constant() {
+        analyze(
+            "
+PROGRAM Bad;
+CONST
+    Max = 10;
+
+BEGIN
+    Max := 11
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Constant expression expected")]
+    fn variable_in_constant() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    i : INTEGER;
+CONST
+    Max = i + 1;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn name_equivalence() {
+        analyze(
+            "
+PROGRAM Bad;
+TYPE
+    TA = RECORD x : INTEGER END;
+    TB = RECORD x : INTEGER END;
+VAR
+    a : TA;
+    b : TB;
+
+BEGIN
+    a := b
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Identifier not found")]
+    fn local_type_out_of_scope() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    x : INTEGER;
+
+PROCEDURE P;
+TYPE
+    Local = 1..5;
+BEGIN
+END;
+
+PROCEDURE Q;
+VAR
+    l : Local;
+BEGIN
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn enum_to_integer() {
+        analyze(
+            "
+PROGRAM Bad;
+TYPE
+    Colour = (Red, Green, Blue);
+VAR
+    i : INTEGER;
+
+BEGIN
+    i := Green
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate id found")]
+    fn duplicate_enum_value() {
+        analyze(
+            "
+PROGRAM Bad;
+TYPE
+    Colour = (Red, Green, Blue);
+    Light = (Amber, Green);
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn set_of_integer() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    s : SET OF INTEGER;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn char_in_integer_set() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    b : BOOLEAN;
+
+BEGIN
+    b := 'a' IN [1, 2]
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Identifier not found")]
+    fn undeclared_pointer_target() {
+        analyze(
+            "
+PROGRAM Bad;
+TYPE
+    PNode = ^TNode;
+VAR
+    p : PNode;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn pointers_to_different_types() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    p : ^INTEGER;
+    q : ^CHAR;
+
+BEGIN
+    p := q
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn pointers_to_types_of_the_same_name() {
+        analyze(
+            "
+PROGRAM Bad;
+TYPE
+    TNode = RECORD
+        value : INTEGER
+    END;
+VAR
+    p : ^TNode;
+
+PROCEDURE Local;
+TYPE
+    TNode = RECORD
+        name : CHAR
+    END;
+VAR
+    q : ^TNode;
+BEGIN
+    q := p
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    fn pointer_to_type_declared_later() {
+        analyze(
+            "
+PROGRAM Good;
+TYPE
+    TNode = RECORD
+        value : INTEGER
+    END;
+
+PROCEDURE Local;
+TYPE
+    PNode = ^TNode;
+    TNode = RECORD
+        name : CHAR;
+        next : PNode
+    END;
+VAR
+    q : PNode;
+BEGIN
+    New(q);
+    q^.name := 'a';
+    q^.next := q^.next^.next
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Constant expression expected")]
+    fn dynamic_array_bound_in_constant() {
+        analyze_turbo(
+            "
+PROGRAM Bad;
+VAR
+    a : ARRAY OF INTEGER;
+    n : INTEGER = High(a);
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn open_array_element_type() {
+        analyze_turbo(
+            "
+PROGRAM Bad;
+VAR
+    reals : ARRAY[1..3] OF REAL;
+
+PROCEDURE Show(values : ARRAY OF INTEGER);
+BEGIN
+END;
+
+BEGIN
+    Show(reals)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only allowed in Turbo Pascal mode")]
+    fn dynamic_array_in_iso_mode() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    a : ARRAY OF INTEGER;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only allowed in Turbo Pascal mode")]
+    fn open_array_in_iso_mode() {
+        analyze(
+            "
+PROGRAM Bad;
+
+PROCEDURE Show(values : ARRAY OF INTEGER);
+BEGIN
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable expected")]
+    fn assign_to_conformant_bound() {
+        analyze(
+            "
+PROGRAM Bad;
+
+PROCEDURE Clear(VAR a : ARRAY[lo..hi : INTEGER] OF INTEGER);
+BEGIN
+    hi := lo
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Type mismatch")]
+    fn procedural_parameter_signature() {
+        analyze(
+            "
+PROGRAM Bad;
+VAR
+    area : REAL;
+
+FUNCTION Integrate(FUNCTION f(x : REAL) : REAL; a, b : REAL) : REAL;
+BEGIN
+    Integrate := (b - a) * f((a + b) / 2)
+END;
+
+FUNCTION Twice(n : INTEGER) : INTEGER;
+BEGIN
+    Twice := 2 * n
+END;
+
+BEGIN
+    area := Integrate(Twice, 0, 1)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Forward declaration not solved")]
+    fn forward_never_defined() {
+        analyze(
+            "
+PROGRAM Bad;
+
+PROCEDURE Later(n : INTEGER); FORWARD;
+
+BEGIN
+    Later(1)
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Heading doesn't match the forward declaration")]
+    fn forward_heading_mismatch() {
+        analyze(
+            "
+PROGRAM Bad;
+
+FUNCTION Twice(n : INTEGER) : INTEGER; FORWARD;
+
+FUNCTION Twice(n : REAL) : INTEGER;
+BEGIN
+    Twice := Round(2 * n)
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate id found")]
+    fn procedure_declared_twice() {
+        analyze(
+            "
+PROGRAM Bad;
+
+PROCEDURE Twice;
+BEGIN
+END;
+
+PROCEDURE Twice;
+BEGIN
+END;
+
+BEGIN
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Nested routine can't be kept in a procedural variable")]
+    fn nested_routine_escapes() {
+        analyze_turbo(
+            "
+PROGRAM Bad;
+TYPE
+    TProc = PROCEDURE;
+VAR
+    saved : TProc;
+
+PROCEDURE Outer;
+VAR
+    count : INTEGER;
+
+    PROCEDURE Inner;
+    BEGIN
+        count := count + 1
+    END;
+
+BEGIN
+    saved := Inner
+END;
+
+BEGIN
+    Outer;
+    saved
+END.",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Nested routine can't be kept in a procedural variable")]
+    fn procedural_parameter_escapes() {
+        analyze_turbo(
+            "
+PROGRAM Bad;
+TYPE
+    TProc = PROCEDURE;
+VAR
+    saved : TProc;
+
+PROCEDURE Keep(PROCEDURE action);
+BEGIN
+    saved := action
+END;
+
+BEGIN
+END.",
+        );
     }
 }
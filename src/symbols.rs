@@ -1,8 +1,14 @@
 use crate::ast::*;
+use crate::builtins;
+#[cfg(test)]
 use crate::error::SemanticError;
+#[cfg(test)]
 use crate::interpreter::NodeVisitor;
-use crate::tokens::Value;
+use crate::tokens::{Token, Value};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
 
 #[derive(Debug, PartialEq)]
 pub struct CallStack {
@@ -27,12 +33,124 @@ impl CallStack {
     pub fn peek(&mut self) -> Option<&mut ActivationRecord> {
         self.records.last_mut()
     }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn get(&self, index: usize) -> &ActivationRecord {
+        &self.records[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut ActivationRecord {
+        &mut self.records[index]
+    }
+}
+
+/// The variables NEW creates. They belong to no activation record, so they
+/// live on until they are disposed of.
+#[derive(Debug, PartialEq)]
+pub struct Heap {
+    /// Disposed variables leave a hole behind, so addresses aren't reused
+    variables: Vec<HeapVariable>,
+}
+
+#[derive(Debug, PartialEq)]
+struct HeapVariable {
+    value: Option<Value>,
+    /// The NEW call that created the variable
+    origin: Token,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap {
+            variables: Vec::new(),
+        }
+    }
+
+    /// Stores a new variable created by the call `origin`, giving its
+    /// address
+    pub fn allocate(&mut self, value: Value, origin: Token) -> usize {
+        self.variables.push(HeapVariable {
+            value: Some(value),
+            origin,
+        });
+        self.variables.len() - 1
+    }
+
+    /// Frees a variable, giving its last value, or None if it was already
+    /// disposed of
+    pub fn dispose(&mut self, address: usize) -> Option<Value> {
+        self.variables[address].value.take()
+    }
+
+    pub fn get(&self, address: usize) -> Option<&Value> {
+        self.variables[address].value.as_ref()
+    }
+
+    pub fn get_mut(&mut self, address: usize) -> Option<&mut Value> {
+        self.variables[address].value.as_mut()
+    }
+
+    /// The addresses of the variables that were never disposed of, with the
+    /// calls that created them
+    pub fn leaks(&self) -> Vec<(usize, &Token)> {
+        self.variables
+            .iter()
+            .enumerate()
+            .filter(|(_, variable)| variable.value.is_some())
+            .map(|(address, variable)| (address, &variable.origin))
+            .collect()
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ARType {
     Program,
     Procedure,
+    Function,
+}
+
+/// Selects a part of a structured value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Component {
+    Index(i32),
+    /// An index counted from zero, whatever the array's bounds
+    Position(i32),
+    Field(String, Vec<Guard>),
+}
+
+/// A variable slot in some activation record, or a component of one, which
+/// is what a VAR parameter is bound to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub record: usize,
+    pub name: String,
+    /// The heap variable the path starts from instead, for designators like
+    /// `p^.next`, and the dereference that reached it
+    pub address: Option<(usize, Token)>,
+    pub path: Vec<Component>,
+}
+
+impl Reference {
+    pub fn new(record: usize, name: String) -> Self {
+        Reference {
+            record,
+            name,
+            address: None,
+            path: Vec::new(),
+        }
+    }
+
+    /// The heap variable at `address`, named after the designator that
+    /// reached it with the dereference `token`
+    pub fn heap(address: usize, name: String, token: Token) -> Self {
+        Reference {
+            address: Some((address, token)),
+            ..Reference::new(0, name)
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -40,16 +158,27 @@ pub struct ActivationRecord {
     name: String,
     type_: ARType,
     pub nesting_level: usize,
+    /// The record of the lexically enclosing block
+    pub access_link: Option<usize>,
     members: HashMap<String, Value>,
+    references: HashMap<String, Reference>,
+    routines: HashMap<String, ProcedureDecl>,
 }
 
 impl ActivationRecord {
+    /// Where a function keeps its result, under a key no identifier can
+    /// spell
+    pub const RESULT: &'static str = "%result";
+
     pub fn new(name: String, type_: ARType, nesting_level: usize) -> Self {
         ActivationRecord {
             name,
             type_,
             nesting_level,
+            access_link: None,
             members: HashMap::new(),
+            references: HashMap::new(),
+            routines: HashMap::new(),
         }
     }
 
@@ -60,13 +189,43 @@ impl ActivationRecord {
     pub fn get(&self, key: String) -> Option<&Value> {
         self.members.get(&key)
     }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.members.get_mut(key)
+    }
+
+    pub fn set_reference(&mut self, key: String, reference: Reference) {
+        self.references.insert(key, reference);
+    }
+
+    pub fn get_reference(&self, key: &str) -> Option<&Reference> {
+        self.references.get(key)
+    }
+
+    /// Whether this is the record of the running function `name`, whose
+    /// result is set by assigning to that name
+    pub fn is_function(&self, name: &str) -> bool {
+        self.type_ == ARType::Function && self.name.to_lowercase() == name
+    }
+
+    pub fn set_routine(&mut self, key: String, routine: ProcedureDecl) {
+        self.routines.insert(key, routine);
+    }
+
+    pub fn get_routine(&self, key: &str) -> Option<&ProcedureDecl> {
+        self.routines.get(key)
+    }
 }
 
+#[cfg(test)]
 pub struct SymbolTableBuilder {
     symtab: SymbolTable,
 }
 
+#[cfg(test)]
 impl NodeVisitor for SymbolTableBuilder {
+    type Output = Value;
+
     fn visit_num(&mut self, _: &mut Node) -> Value {
         Value::None
     }
@@ -135,10 +294,10 @@ impl NodeVisitor for SymbolTableBuilder {
     }
 
     fn visit_var_decl(&mut self, var_decl: &mut Node) -> Value {
-        if let Node::VarDecl(var_node, type_node) = var_decl {
+        if let Node::VarDecl(var_node, type_node, _) = var_decl {
             let type_symbol = self
                 .symtab
-                .lookup(type_node.value.expect_string(), false)
+                .lookup(type_node.token().value.expect_string(), false)
                 .unwrap();
             let var_name = var_node.value.expect_string();
             let var_symbol = VarSymbol::new(var_name, type_symbol.clone());
@@ -149,6 +308,14 @@ impl NodeVisitor for SymbolTableBuilder {
         Value::None
     }
 
+    fn visit_const_decl(&mut self, _: &mut Node) -> Value {
+        Value::None
+    }
+
+    fn visit_type_decl(&mut self, _: &mut Node) -> Value {
+        Value::None
+    }
+
     fn visit_type(&mut self, _: &mut Type) -> Value {
         Value::None
     }
@@ -160,13 +327,95 @@ impl NodeVisitor for SymbolTableBuilder {
     fn visit_procedure_call(&mut self, _: &mut ProcedureCall) -> Value {
         Value::None
     }
+
+    fn visit_if(&mut self, if_: &mut Node) -> Value {
+        if let Node::If(_, condition, consequence, alternative) = if_ {
+            self.visit(condition);
+            self.visit(consequence);
+            if let Some(alternative) = alternative {
+                self.visit(alternative);
+            }
+        }
+
+        Value::None
+    }
+
+    fn visit_while(&mut self, while_: &mut Node) -> Value {
+        if let Node::While(_, condition, body) = while_ {
+            self.visit(condition);
+            self.visit(body);
+        }
+
+        Value::None
+    }
+
+    fn visit_repeat(&mut self, repeat: &mut Node) -> Value {
+        if let Node::Repeat(_, body, condition) = repeat {
+            for statement in body {
+                self.visit(statement);
+            }
+            self.visit(condition);
+        }
+
+        Value::None
+    }
+
+    fn visit_for(&mut self, for_: &mut Node) -> Value {
+        if let Node::For(control, start, _, end, body) = for_ {
+            self.visit_var(control);
+            self.visit(start);
+            self.visit(end);
+            self.visit(body);
+        }
+
+        Value::None
+    }
+
+    fn visit_case(&mut self, case: &mut Node) -> Value {
+        if let Node::Case(_, selector, arms, otherwise) = case {
+            self.visit(selector);
+            for arm in arms {
+                self.visit(&mut arm.statement);
+            }
+            for statement in otherwise.iter_mut().flatten() {
+                self.visit(statement);
+            }
+        }
+
+        Value::None
+    }
+
+    fn visit_set(&mut self, set: &mut Node) -> Value {
+        if let Node::Set(_, elements) = set {
+            for (low, high) in elements {
+                self.visit(low);
+                if let Some(high) = high {
+                    self.visit(high);
+                }
+            }
+        }
+
+        Value::None
+    }
+
+    fn visit_formatted(&mut self, formatted: &mut Node) -> Value {
+        if let Node::Formatted(_, expr, width, precision) = formatted {
+            self.visit(expr);
+            self.visit(width);
+            if let Some(precision) = precision {
+                self.visit(precision);
+            }
+        }
+
+        Value::None
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct SymbolTable {
     symbols: HashMap<String, Symbol>,
     pub scope_level: usize,
-    scope_name: String,
+    pub scope_name: String,
     pub enclosing_scope: Option<Box<SymbolTable>>,
 }
 
@@ -182,22 +431,32 @@ impl SymbolTable {
             scope_name,
             enclosing_scope: enclosing_scope.map(Box::new),
         };
-        symtab.init_builtins();
+        // Nested scopes find the builtins through the outermost one, so
+        // declarations anywhere can hide them
+        if symtab.enclosing_scope.is_none() {
+            symtab.init_builtins();
+        }
         symtab
     }
 
     fn init_builtins(&mut self) {
         self.insert(Symbol::Builtin(String::from("INTEGER")));
         self.insert(Symbol::Builtin(String::from("REAL")));
+        self.insert(Symbol::Builtin(String::from("BOOLEAN")));
+        self.insert(Symbol::Builtin(String::from("STRING")));
+        self.insert(Symbol::Builtin(String::from("CHAR")));
+        for name in builtins::ROUTINES {
+            self.insert(Symbol::BuiltinProcedure(String::from(*name)));
+        }
     }
 
     pub fn insert(&mut self, symbol: Symbol) {
-        self.symbols.insert(symbol.name(), symbol);
+        self.symbols.insert(symbol.name().to_lowercase(), symbol);
     }
 
+    /// Looks up an identifier; like Pascal itself, this ignores case
     pub fn lookup(&self, name: String, current_scope_only: bool) -> Option<&Symbol> {
-        println!("Looking up {} in scope {}", &name, self.scope_name);
-        self.symbols.get(&name).or_else(|| {
+        self.symbols.get(&name.to_lowercase()).or_else(|| {
             if current_scope_only {
                 None
             } else {
@@ -214,15 +473,210 @@ impl SymbolTable {
 pub enum Symbol {
     Builtin(String),
     Var(Box<VarSymbol>),
+    Const(Box<ConstSymbol>),
+    /// A type identifier declared in a TYPE section
+    Type(Box<TypeSymbol>),
+    /// A type declared with a name. Types are the same when they have the
+    /// same name, even if their structure matches some other type's. Every
+    /// use of the type shares the declaration's structure.
+    Named(String, Rc<Symbol>),
     Procedure(ProcedureSymbol),
+    /// The names of the values of an enumerated type, in order
+    Enum(Rc<Vec<String>>),
+    /// Base type and inclusive bounds
+    Subrange(Box<Symbol>, i32, i32),
+    /// Index type and element type
+    Array(Box<Symbol>, Box<Symbol>),
+    /// The element type of an array indexed from zero, whose length is set
+    /// at run time
+    DynArray(Box<Symbol>),
+    /// Index type and element type of a conformant array parameter
+    Conformant(Box<Symbol>, Box<Symbol>),
+    /// The element type of an open array parameter
+    OpenArray(Box<Symbol>),
+    Record(Vec<FieldSymbol>),
+    /// A set of values of the element type, which the empty set `[]`
+    /// doesn't have
+    Set(Option<Box<Symbol>>),
+    /// `STRING[n]`, a STRING of at most n characters
+    String(i32),
+    /// A pointer to variables of the target type, which NIL doesn't have
+    Pointer(Option<PointerTarget>),
+    /// A procedural type, taking routines with the parameters and result of
+    /// the nameless procedure it holds
+    Procedural(Box<ProcedureSymbol>),
+    /// A procedure or function the interpreter provides itself
+    BuiltinProcedure(String),
 }
 
 impl Symbol {
+    pub fn builtin(name: &str) -> Self {
+        Symbol::Builtin(String::from(name))
+    }
+
+    /// The structure of a type, looking through the name it was declared
+    /// with
+    pub fn definition(&self) -> &Symbol {
+        match self {
+            Symbol::Named(_, type_) => type_.definition(),
+            _ => self,
+        }
+    }
+
+    /// The type a subrange is taken from, or the type itself
+    pub fn base(&self) -> &Symbol {
+        match self.definition() {
+            Symbol::Subrange(base, ..) => base,
+            _ => self,
+        }
+    }
+
+    pub fn is_builtin(&self, name: &str) -> bool {
+        match self.base().definition() {
+            Symbol::Builtin(b) => b == name,
+            Symbol::String(_) => name == "STRING",
+            _ => false,
+        }
+    }
+
+    pub fn is_string(&self) -> bool {
+        self.is_builtin("STRING")
+    }
+
+    pub fn is_char(&self) -> bool {
+        self.is_builtin("CHAR")
+    }
+
+    /// Whether values of this type can be used as a STRING
+    pub fn is_text(&self) -> bool {
+        self.is_string() || self.is_char()
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_builtin("INTEGER") || self.is_builtin("REAL")
+    }
+
+    pub fn is_enum(&self) -> bool {
+        matches!(self.base().definition(), Symbol::Enum(_))
+    }
+
+    pub fn is_ordinal(&self) -> bool {
+        self.is_builtin("INTEGER") || self.is_builtin("BOOLEAN") || self.is_char() || self.is_enum()
+    }
+
+    /// Whether this and `other` are set types that can be combined and
+    /// compared: their elements have the same base type, or one of them is
+    /// the type of `[]`
+    pub fn is_set_compatible(&self, other: &Symbol) -> bool {
+        match (self.definition(), other.definition()) {
+            (Symbol::Set(Some(left)), Symbol::Set(Some(right))) => left.base() == right.base(),
+            (Symbol::Set(_), Symbol::Set(_)) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_dynamic_array(&self) -> bool {
+        matches!(self.definition(), Symbol::DynArray(_))
+    }
+
+    /// Whether this is the type of a conformant or open array parameter,
+    /// which takes arrays of more than one type
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.definition(),
+            Symbol::Conformant(..) | Symbol::OpenArray(_)
+        )
+    }
+
+    /// Whether the arrays of this type only know their bounds at run time
+    pub fn has_dynamic_bounds(&self) -> bool {
+        self.is_open() || self.is_dynamic_array()
+    }
+
+    /// Whether an argument of type `actual` can be passed to a parameter of
+    /// this conformant or open array type
+    pub fn conforms(&self, actual: &Symbol) -> bool {
+        match (self.definition(), actual.definition()) {
+            (
+                Symbol::OpenArray(element),
+                Symbol::Array(_, actual) | Symbol::DynArray(actual) | Symbol::OpenArray(actual),
+            ) => element == actual,
+            (
+                Symbol::Conformant(index, element),
+                Symbol::Array(actual_index, actual) | Symbol::Conformant(actual_index, actual),
+            ) => {
+                index.base() == actual_index.base()
+                    && (element == actual || element.conforms(actual))
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self.definition(), Symbol::Pointer(_))
+    }
+
+    /// Whether this and `other` are pointer types that can be assigned and
+    /// compared: they point to the same type, or one of them is the type of
+    /// NIL
+    pub fn is_pointer_compatible(&self, other: &Symbol) -> bool {
+        match (self.definition(), other.definition()) {
+            (Symbol::Pointer(Some(left)), Symbol::Pointer(Some(right))) => left == right,
+            (Symbol::Pointer(_), Symbol::Pointer(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this is a procedural type that can hold `other`, a routine or
+    /// a value of a procedural type with the same parameters and result
+    pub fn is_procedural_compatible(&self, other: &Symbol) -> bool {
+        match (self.definition(), other.signature()) {
+            (Symbol::Procedural(target), Some(source)) => target.same_signature(source),
+            _ => false,
+        }
+    }
+
+    /// The parameters and result of a routine, or of the routines a
+    /// procedural type holds
+    pub fn signature(&self) -> Option<&ProcedureSymbol> {
+        match self.definition() {
+            Symbol::Procedure(routine) => Some(routine),
+            Symbol::Procedural(routine) => Some(routine),
+            _ => None,
+        }
+    }
+
+    /// The smallest and largest ordinal value of a finite ordinal type
+    pub fn bounds(&self) -> Option<(i32, i32)> {
+        match self.definition() {
+            Symbol::Subrange(_, low, high) => Some((*low, *high)),
+            Symbol::Enum(names) => Some((0, names.len() as i32 - 1)),
+            _ if self.is_builtin("BOOLEAN") => Some((0, 1)),
+            _ if self.is_char() => Some((0, 255)),
+            _ => None,
+        }
+    }
+
     fn name(&self) -> String {
         match self {
             Symbol::Builtin(b) => b.clone(),
             Symbol::Var(v) => v.name.clone(),
+            Symbol::Const(c) => c.name.clone(),
+            Symbol::Type(t) => t.name.clone(),
+            Symbol::Named(name, _) => name.clone(),
             Symbol::Procedure(p) => p.name.clone(),
+            Symbol::BuiltinProcedure(p) => p.clone(),
+            Symbol::Enum(_)
+            | Symbol::Subrange(..)
+            | Symbol::Array(..)
+            | Symbol::DynArray(_)
+            | Symbol::Conformant(..)
+            | Symbol::OpenArray(_)
+            | Symbol::Record(..)
+            | Symbol::Set(_)
+            | Symbol::String(_)
+            | Symbol::Pointer(_)
+            | Symbol::Procedural(_) => String::new(),
         }
     }
 }
@@ -230,12 +684,133 @@ impl Symbol {
 #[derive(Clone, Debug, PartialEq)]
 pub struct VarSymbol {
     pub name: String,
-    type_: Symbol,
+    pub type_: Symbol,
+    pub by_reference: bool,
+    /// Set for the bound identifiers of conformant arrays, which can be read
+    /// like variables but not changed
+    pub read_only: bool,
 }
 
 impl VarSymbol {
     pub fn new(name: String, type_: Symbol) -> Self {
-        VarSymbol { name, type_ }
+        VarSymbol {
+            name,
+            type_,
+            by_reference: false,
+            read_only: false,
+        }
+    }
+}
+
+/// A constant, whose value is known at compile time and replaces its name
+/// wherever it is used
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstSymbol {
+    pub name: String,
+    pub type_: Symbol,
+    pub value: Value,
+}
+
+impl ConstSymbol {
+    pub fn new(name: String, type_: Symbol, value: Value) -> Self {
+        ConstSymbol { name, type_, value }
+    }
+}
+
+/// The name given to a type in a TYPE section. An alias like
+/// `Count = INTEGER` stands for the very same type, while a new structure
+/// gets a type of its own named after the declaration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeSymbol {
+    pub name: String,
+    pub type_: Symbol,
+}
+
+impl TypeSymbol {
+    pub fn new(name: String, type_: Symbol) -> Self {
+        TypeSymbol { name, type_ }
+    }
+}
+
+/// The type a pointer type points to. It may be declared after the pointer
+/// type, as in `PNode = ^TNode`, so it is filled in once the TYPE section
+/// around it is done. Pointer types are the same when their targets are,
+/// with declared types compared by declaration rather than by structure,
+/// which would never end for records that point to their own type.
+#[derive(Clone)]
+pub struct PointerTarget {
+    pub name: String,
+    type_: Rc<RefCell<Option<Symbol>>>,
+}
+
+impl PointerTarget {
+    pub fn new(name: String) -> Self {
+        PointerTarget {
+            name,
+            type_: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn resolve(&self, type_: Symbol) {
+        *self.type_.borrow_mut() = Some(type_);
+    }
+
+    /// The type pointed to, which is only known after resolving
+    pub fn type_(&self) -> Symbol {
+        self.type_.borrow().clone().unwrap()
+    }
+}
+
+impl PartialEq for PointerTarget {
+    fn eq(&self, other: &Self) -> bool {
+        if Rc::ptr_eq(&self.type_, &other.type_) {
+            return true;
+        }
+        match (&*self.type_.borrow(), &*other.type_.borrow()) {
+            (Some(Symbol::Named(_, left)), Some(Symbol::Named(_, right))) => {
+                Rc::ptr_eq(left, right)
+            }
+            (Some(left), Some(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for PointerTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "^{}", self.name)
+    }
+}
+
+/// A field of a record type. Fields of a variant part are stored alongside
+/// the fixed ones, guarded by the tag values that make them accessible.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldSymbol {
+    pub name: String,
+    pub type_: Symbol,
+    pub guards: Vec<Guard>,
+}
+
+impl FieldSymbol {
+    pub fn new(name: String, type_: Symbol, guards: Vec<Guard>) -> Self {
+        FieldSymbol {
+            name,
+            type_,
+            guards,
+        }
+    }
+}
+
+/// Requires the tag field of a record to hold one of the labels of a variant
+#[derive(Clone, Debug, PartialEq)]
+pub struct Guard {
+    pub tag: String,
+    pub labels: Vec<(i32, i32)>,
+}
+
+impl Guard {
+    pub fn new(tag: String, labels: Vec<(i32, i32)>) -> Self {
+        Guard { tag, labels }
     }
 }
 
@@ -243,7 +818,11 @@ impl VarSymbol {
 pub struct ProcedureSymbol {
     name: String,
     pub formal_params: Vec<VarSymbol>,
-    pub block_ast: Option<Box<Block>>,
+    pub return_type: Option<Box<Symbol>>,
+    /// Whether it's declared inside another routine, whose record it needs
+    /// to run. Procedural parameters are nested as well, since they may hold
+    /// such routines.
+    pub nested: bool,
 }
 
 impl ProcedureSymbol {
@@ -251,9 +830,41 @@ impl ProcedureSymbol {
         ProcedureSymbol {
             name,
             formal_params,
-            block_ast: None,
+            return_type: None,
+            nested: false,
         }
     }
+
+    /// Whether both take the same kinds of parameters, of the same types, and
+    /// give the same result. The names of the parameters don't matter, and
+    /// neither does where the routines are declared.
+    pub fn same_signature(&self, other: &ProcedureSymbol) -> bool {
+        let same_result = match (&self.return_type, &other.return_type) {
+            (Some(left), Some(right)) => same_parameter_type(left, right),
+            (None, None) => true,
+            _ => false,
+        };
+        self.formal_params.len() == other.formal_params.len()
+            && self
+                .formal_params
+                .iter()
+                .zip(&other.formal_params)
+                .all(|(left, right)| {
+                    left.by_reference == right.by_reference
+                        && same_parameter_type(&left.type_, &right.type_)
+                })
+            && same_result
+    }
+}
+
+/// Whether a parameter or result of one routine has the type of the
+/// corresponding one of another. Procedural parameters are alike when their
+/// signatures are.
+fn same_parameter_type(left: &Symbol, right: &Symbol) -> bool {
+    match (left, right) {
+        (Symbol::Procedural(left), Symbol::Procedural(right)) => left.same_signature(right),
+        _ => left == right,
+    }
 }
 
 #[cfg(test)]
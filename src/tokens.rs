@@ -1,12 +1,26 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
     // Types
     Integer,
     Real,
+    Boolean,
+    String,
+    Char,
+    Array,
+    Record,
+    Set,
+    Nil,
     IntegerConst,
     RealConst,
+    StringConst,
+    CharConst,
+    True,
+    False,
 
     // Operators
     Plus,
@@ -14,15 +28,47 @@ pub enum TokenType {
     Mul,
     IntegerDiv,
     FloatDiv,
+    Mod,
+    Shl,
+    Shr,
     RightParen,
     LeftParen,
+    LeftBracket,
+    RightBracket,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+    And,
+    Or,
+    Xor,
+    Not,
+    In,
 
     // Reserved keywords
     Program,
+    Const,
+    Type,
     Var,
     Begin,
     End,
     Procedure,
+    Function,
+    If,
+    Then,
+    Else,
+    While,
+    Do,
+    Repeat,
+    Until,
+    For,
+    To,
+    Downto,
+    Case,
+    Of,
+    Otherwise,
 
     ID,
     Assign,
@@ -30,16 +76,39 @@ pub enum TokenType {
     Dot,
     Colon,
     Comma,
+    Range,
+    Caret,
 
     Eof,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Default)]
 pub enum Value {
     Float(f32),
     Integer(i32),
+    Boolean(bool),
     Char(char),
     String(String),
+    /// The ordinal of a value of an enumerated type, and the names of all
+    /// the type's values
+    Enum(i32, Rc<Vec<String>>),
+    /// Lower bound and elements
+    Array(i32, Vec<Value>),
+    /// The elements of an `ARRAY OF T`, which are shared by every variable
+    /// the array is assigned to until SetLength gives one of them a copy
+    DynArray(Rc<RefCell<Vec<Value>>>),
+    /// Field names and values, in declaration order
+    Record(Vec<(String, Value)>),
+    /// The ordinals of the elements of a set
+    Set(BTreeSet<i32>),
+    /// The address of a variable on the heap, or NIL
+    Pointer(Option<usize>),
+    /// A procedure or function, with the activation record of the block
+    /// declaring it, which it keeps as its static environment. Nested
+    /// routines are only passed to procedural parameters, so that record is
+    /// still on the call stack whenever the routine runs.
+    Routine(usize, String),
+    #[default]
     None,
 }
 
@@ -47,9 +116,46 @@ impl Value {
     pub fn expect_string(&self) -> String {
         match self {
             Value::String(s) => s.to_string(),
+            Value::Char(c) => c.to_string(),
             _ => panic!("Expected string"),
         }
     }
+
+    pub fn expect_boolean(&self) -> bool {
+        match self {
+            Value::Boolean(b) => *b,
+            _ => panic!("Expected boolean"),
+        }
+    }
+
+    pub fn expect_ordinal(&self) -> i32 {
+        match self {
+            Value::Integer(i) => *i,
+            Value::Boolean(b) => *b as i32,
+            Value::Char(c) => *c as i32,
+            Value::Enum(ordinal, _) => *ordinal,
+            _ => panic!("Expected ordinal"),
+        }
+    }
+
+    /// Builds a value of the same ordinal type as `self` from an ordinal number
+    pub fn with_ordinal(&self, ordinal: i32) -> Value {
+        match self {
+            Value::Integer(_) => Value::Integer(ordinal),
+            Value::Boolean(_) => Value::Boolean(ordinal != 0),
+            Value::Char(_) => Value::Char(char::from_u32(ordinal as u32).unwrap()),
+            Value::Enum(_, names) => Value::Enum(ordinal, names.clone()),
+            _ => panic!("Expected ordinal"),
+        }
+    }
+
+    pub fn expect_number(&self) -> f32 {
+        match self {
+            Value::Integer(i) => *i as f32,
+            Value::Float(f) => *f,
+            _ => panic!("Expected number"),
+        }
+    }
 }
 
 impl Display for Value {
@@ -57,8 +163,32 @@ impl Display for Value {
         match self {
             Value::Float(v) => write!(f, "{}", v),
             Value::Integer(v) => write!(f, "{}", v),
+            Value::Boolean(v) => write!(f, "{}", if *v { "TRUE" } else { "FALSE" }),
             Value::Char(v) => write!(f, "{}", v),
+            Value::Enum(ordinal, names) => write!(f, "{}", names[*ordinal as usize]),
             Value::String(v) => write!(f, "{}", v),
+            Value::Array(_, elements) => {
+                let elements: Vec<_> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::DynArray(elements) => {
+                let elements: Vec<_> = elements.borrow().iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Record(fields) => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                write!(f, "({})", fields.join("; "))
+            }
+            Value::Set(elements) => {
+                let elements: Vec<_> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Pointer(Some(address)) => write!(f, "^{}", address),
+            Value::Pointer(None) => write!(f, "NIL"),
+            Value::Routine(_, name) => write!(f, "{}", name),
             Value::None => write!(f, ""),
         }
     }
@@ -81,6 +211,11 @@ impl Token {
             column,
         }
     }
+
+    /// Where the token is in the source, as `line:column`
+    pub fn position(&self) -> String {
+        format!("{}:{}", self.lineno, self.column)
+    }
 }
 
 impl Display for Token {
@@ -88,10 +223,7 @@ impl Display for Token {
         write!(
             f,
             "Token({:?}, {}, position={}:{})",
-            self.type_,
-            self.value.to_string(),
-            self.lineno,
-            self.column
+            self.type_, self.value, self.lineno, self.column
         )
     }
 }